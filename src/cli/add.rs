@@ -1,12 +1,75 @@
+use std::io::{self, Write};
 use std::path::PathBuf;
 
-use crate::fs_tools::dirs;
+use serde::Serialize;
 
-/// cmd of `init [PATHSPEC]...`
-pub fn cmd_add(pathspec: Vec<PathBuf>) {
-    dirs::check_init();
+use ugit_rs::fs_tools::pathspec::Pathspec;
+use ugit_rs::objects::db::insert::ObjectInsert;
+use ugit_rs::{Error, Repository, Result};
 
-    for path in pathspec {
-        println!("{:?}", path)
+use super::output::{self, json_path, JsonPath, OutputFormat};
+
+#[derive(Serialize)]
+struct JsonAddedFile {
+    path: JsonPath,
+    oid: String,
+}
+
+#[derive(Serialize)]
+struct JsonAdd {
+    added: Vec<JsonAddedFile>,
+}
+
+/// cmd of `add [-A] [PATHSPEC]...`
+///
+/// stores the content of every (not ignored) file selected by `pathspec` in the
+/// object database. like Git, an empty pathspec adds nothing, unless `all` is given.
+pub fn cmd_add(
+    repo: &Repository,
+    pathspec: Vec<PathBuf>,
+    all: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    if pathspec.is_empty() && !all {
+        return Err(Error::invalid_argument(
+            "nothing specified, nothing added. maybe you wanted to say 'add .'?",
+        ));
+    }
+
+    let pathspec = Pathspec::new(&pathspec, repo.work_tree())?;
+    let mut skipped = vec![];
+    let files = repo
+        .build_tree(repo.work_tree(), &pathspec, &mut skipped)?
+        .files();
+    output::warn_skipped(&skipped);
+
+    // like Git, every pathspec has to select something.
+    for item in pathspec.items().iter().filter(|item| !item.is_exclude()) {
+        if !files.iter().any(|(path, _, _)| item.matches(path)) {
+            return Err(Error::invalid_argument(format!(
+                "pathspec '{}' did not match any files",
+                item
+            )));
+        }
+    }
+
+    let mut out = io::stdout().lock();
+    let mut added = vec![];
+    for (path, _, blob) in files {
+        let oid = blob.insert_into_db()?;
+
+        match format {
+            OutputFormat::Text => writeln!(out, "add '{}'", path.display())?,
+            OutputFormat::Json => added.push(JsonAddedFile {
+                path: json_path(&path),
+                oid: oid.to_string(),
+            }),
+        }
     }
+
+    if format == OutputFormat::Json {
+        output::print_json(&JsonAdd { added })?;
+    }
+
+    Ok(())
 }
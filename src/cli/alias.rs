@@ -0,0 +1,243 @@
+use std::ffi::OsString;
+use std::process::{self, Command};
+
+use clap::Parser;
+
+use ugit_rs::config::{Config, ConfigEntry};
+use ugit_rs::error::IoResultExt;
+use ugit_rs::{Error, Result};
+
+use super::external::repository_env;
+use super::{locate_repository, setup_location, Cli, Commands, Location};
+
+/// the prefix of the config variables defining aliases, `alias.<name>`.
+const ALIAS_SECTION: &str = "alias";
+
+/// expand `command` while it's an alias, `alias.<name>` of the config.
+///
+/// an alias expands to a command line of ugit-rs, whose global options apply on top of
+/// `location`, followed by the arguments given to the alias. an alias starting with `!`
+/// is a shell command instead, which is run with those arguments and the
+/// [`repository_env`], and ugit-rs exits with its exit code.
+///
+/// fails with [`Error::InvalidArgument`] if an alias expands to itself, directly or not.
+pub fn expand(
+    location: Location,
+    command: Option<Commands>,
+) -> Result<(Location, Option<Commands>)> {
+    expand_with(location, command, |location| {
+        let ugit_dir = locate_repository(location)?.map(|(ugit_dir, _)| ugit_dir);
+        Config::load(ugit_dir.as_deref())
+    })
+}
+
+/// [`expand`], where the aliases are read from the config `load_config` returns for
+/// the location of each alias.
+fn expand_with<F>(
+    mut location: Location,
+    mut command: Option<Commands>,
+    mut load_config: F,
+) -> Result<(Location, Option<Commands>)>
+where
+    F: FnMut(&Location) -> Result<Config>,
+{
+    let mut expanded_names: Vec<String> = vec![];
+
+    while let Some(Commands::External(args)) = &command {
+        let name = args[0].to_string_lossy().into_owned();
+        let config = load_config(&location)?;
+
+        let entry = match config.get(&format!("{}.{}", ALIAS_SECTION, name)) {
+            Some(entry) => entry,
+            // not an alias, left to the caller.
+            None => break,
+        };
+
+        if expanded_names.contains(&name) {
+            expanded_names.push(name);
+            return Err(Error::invalid_argument(format!(
+                "alias loop detected: {}",
+                expanded_names.join(" -> ")
+            )));
+        }
+        expanded_names.push(name);
+
+        let value = entry.as_str()?;
+        if let Some(shell_command) = value.strip_prefix('!') {
+            process::exit(run_shell_alias(&location, shell_command, &args[1..])?);
+        }
+
+        let words = split_words(entry, value)?;
+        let program = OsString::from(env!("CARGO_PKG_NAME"));
+        let cli = Cli::try_parse_from(
+            std::iter::once(program)
+                .chain(words.into_iter().map(OsString::from))
+                .chain(args[1..].iter().cloned()),
+        )
+        .unwrap_or_else(|e| e.exit());
+
+        let alias_location = setup_location(&cli)?;
+        location = Location {
+            ugit_dir: alias_location.ugit_dir.or(location.ugit_dir),
+            work_tree: alias_location.work_tree.or(location.work_tree),
+        };
+        command = cli.command;
+    }
+
+    Ok((location, command))
+}
+
+/// run `shell_command` with `sh -c`, with `args` as its positional parameters,
+/// return its exit code.
+fn run_shell_alias(location: &Location, shell_command: &str, args: &[OsString]) -> Result<i32> {
+    let script = match args.is_empty() {
+        true => shell_command.to_owned(),
+        false => format!("{} \"$@\"", shell_command),
+    };
+
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(script)
+        .arg(shell_command)
+        .args(args)
+        .envs(repository_env(location)?)
+        .status()
+        .with_path("sh")?;
+
+    // killed by a signal, like a shell reports it.
+    Ok(status.code().unwrap_or(128))
+}
+
+/// the words of `value`, split at whitespace like a shell does. whitespace within
+/// `'` or `"` quotes, or escaped by `\`, is kept.
+fn split_words(entry: &ConfigEntry, value: &str) -> Result<Vec<String>> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (None, c) if c.is_whitespace() => {
+                words.extend(word.take());
+            }
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (Some(open), c) if c == open => quote = None,
+            (None | Some('"'), '\\') => match chars.next() {
+                Some(escaped) => word.get_or_insert_with(String::new).push(escaped),
+                None => return Err(entry.invalid("alias ends with an unescaped '\\'")),
+            },
+            (_, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err(entry.invalid("unclosed quote in alias"));
+    }
+    words.extend(word);
+
+    if words.is_empty() {
+        return Err(entry.invalid(format!("empty alias for '{}'", entry.key())));
+    }
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use ugit_rs::config::{ConfigFile, ConfigScope};
+
+    use super::*;
+
+    /// the config read from a file with `content`.
+    fn config(name: &str, content: &str) -> Config {
+        let path = env::temp_dir().join(format!("ugit-rs-alias-{}-{}", process::id(), name));
+        fs::write(&path, content).unwrap();
+        let file = ConfigFile::read(&path, ConfigScope::Local).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        Config::from_iter(file.entries().cloned())
+    }
+
+    /// the words of an alias whose value, once read from its config file, is `value`.
+    fn words(value: &str) -> Result<Vec<String>> {
+        let config = config("words", "[alias]\n\ta = a\n");
+
+        split_words(config.get("alias.a").unwrap(), value)
+    }
+
+    fn location() -> Location {
+        Location {
+            ugit_dir: None,
+            work_tree: None,
+        }
+    }
+
+    fn external(name: &str) -> Option<Commands> {
+        Some(Commands::External(vec![OsString::from(name)]))
+    }
+
+    #[test]
+    fn words_are_split_like_a_shell_does() {
+        assert_eq!(words("ls-tree  -r   -l").unwrap(), ["ls-tree", "-r", "-l"]);
+        assert_eq!(
+            words("x 'a b' \"c d\" e").unwrap(),
+            ["x", "a b", "c d", "e"]
+        );
+        assert_eq!(words("'a'\"b\"c").unwrap(), ["abc"]);
+        assert_eq!(words("x '' \"\"").unwrap(), ["x", "", ""]);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(words(r"a\ b").unwrap(), ["a b"]);
+        assert_eq!(words(r#""a \" b\\""#).unwrap(), [r#"a " b\"#]);
+        assert_eq!(words(r#"a\'b\"c"#).unwrap(), [r#"a'b"c"#]);
+        // single quotes keep a `\` as it is.
+        assert_eq!(words(r"'a\b'").unwrap(), [r"a\b"]);
+    }
+
+    #[test]
+    fn invalid_words() {
+        for value in ["'unclosed", r#""unclosed\""#, "a 'b' \"c", r"a \", "", "  "] {
+            assert!(
+                matches!(words(value), Err(Error::InvalidConfig { .. })),
+                "{:?} is split",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn aliases_expand_to_commands() {
+        let config = config("chain", "[alias]\n\tx = y\n\ty = write-tree\n");
+        let (_, command) = expand_with(location(), external("x"), |_| Ok(config.clone())).unwrap();
+
+        assert!(matches!(command, Some(Commands::WriteTree { .. })));
+    }
+
+    #[test]
+    fn alias_loops_are_detected() {
+        let config = config("loop", "[alias]\n\ta = b\n\tb = a\n\tself = self\n");
+
+        match expand_with(location(), external("a"), |_| Ok(config.clone())) {
+            Err(Error::InvalidArgument(message)) => {
+                assert_eq!(message, "alias loop detected: a -> b -> a")
+            }
+            other => panic!(
+                "expected a loop, got {:?}",
+                other.map(|(_, command)| command)
+            ),
+        }
+        assert!(matches!(
+            expand_with(location(), external("self"), |_| Ok(config.clone())),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,365 @@
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+use std::process::ExitCode;
+
+use clap::Args;
+use serde::Serialize;
+
+use ugit_rs::crypto::ObjectId;
+use ugit_rs::fs_tools::files;
+use ugit_rs::objects::blob::BlobObject;
+use ugit_rs::objects::db::query;
+use ugit_rs::objects::type_literal::ObjectTypeLiteral;
+use ugit_rs::objects::Object;
+use ugit_rs::{Error, Repository, Result};
+
+use super::check_object;
+use super::output::{self, JsonTreeEntry, OutputFormat};
+
+/// what `cat-file` prints, at most one of them. the content as it's stored by default,
+/// which `-p` pretty-prints.
+#[derive(Args, Debug)]
+#[group(multiple = false)]
+pub struct CatFileMode {
+    /// Print the type of the object.
+    #[arg(short = 't')]
+    pub show_type: bool,
+    /// Print the size of the object.
+    #[arg(short = 's')]
+    pub show_size: bool,
+    /// Exit with 0 if the object exists, or with 1 if it does not. Nothing is printed.
+    #[arg(short = 'e')]
+    pub exists: bool,
+    /// Pretty-print the content of the object, by its type: the entries of a tree one per
+    /// line, instead of as they're stored.
+    #[arg(short = 'p')]
+    pub pretty: bool,
+    /// Print the content of the object, which must be of <type>.
+    #[arg(long = "expect-type", value_name = "type", value_enum)]
+    pub expected_type: Option<ObjectTypeLiteral>,
+    /// Print "<oid> <type> <size>" and the content of each object whose oid is read from
+    /// stdin, one per line. An object which does not exist is printed as "<oid> missing",
+    /// and one which can't be read as "<oid> corrupt", with the error on stderr.
+    #[arg(long)]
+    pub batch: bool,
+    /// Like --batch, without the content.
+    #[arg(long = "batch-check")]
+    pub batch_check: bool,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonCatFile {
+    Tree {
+        oid: String,
+        entries: Vec<JsonTreeEntry>,
+    },
+}
+
+/// read `reader` in chunks, and call `f` with each of them as text, split at char
+/// boundaries. stops and returns `false` at the first bytes which are not UTF-8.
+fn read_utf8_chunks<R, F>(mut reader: R, mut f: F) -> Result<bool>
+where
+    R: Read,
+    F: FnMut(&str) -> Result<()>,
+{
+    let mut buf = vec![0; files::STREAM_CHUNK_SIZE];
+    // the bytes of a char split by the previous chunk, at the start of `buf`.
+    let mut pending = 0;
+
+    loop {
+        let n = match reader.read(&mut buf[pending..]) {
+            Ok(0) => return Ok(pending == 0),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+
+        let filled = pending + n;
+        let (text, valid_len) = match std::str::from_utf8(&buf[..filled]) {
+            Ok(text) => (text, filled),
+            // a char which continues in the next chunk.
+            Err(e) if e.error_len().is_none() => (
+                std::str::from_utf8(&buf[..e.valid_up_to()]).expect("the prefix is UTF-8"),
+                e.valid_up_to(),
+            ),
+            Err(_) => return Ok(false),
+        };
+        f(text)?;
+
+        buf.copy_within(valid_len..filled, 0);
+        pending = filled - valid_len;
+    }
+}
+
+/// print the blob with `oid` as JSON, see [`super::output`].
+///
+/// the content is streamed like the text output: it's read once to find its encoding,
+/// and once more while it's printed, so it's never held in memory as a whole.
+fn print_json_blob(oid: &ObjectId, blob: &BlobObject) -> Result<()> {
+    let is_utf8 = read_utf8_chunks(blob.origin_reader()?, |_| Ok(()))?;
+    let encoding = match is_utf8 {
+        true => "utf-8",
+        false => "hex",
+    };
+
+    let mut out = io::stdout().lock();
+    write!(
+        out,
+        "{{\"type\":\"blob\",\"oid\":{},\"size\":{},\"encoding\":{},\"content\":\"",
+        output::to_json(&oid.to_string())?,
+        blob.size()?,
+        output::to_json(encoding)?
+    )?;
+
+    if is_utf8 {
+        read_utf8_chunks(blob.origin_reader()?, |text| {
+            let quoted = output::to_json(text)?;
+            out.write_all(&quoted.as_bytes()[1..quoted.len() - 1])?;
+            Ok(())
+        })?;
+    } else {
+        let mut reader = blob.origin_reader()?;
+        let mut buf = vec![0; files::STREAM_CHUNK_SIZE];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => out.write_all(hex::encode(&buf[..n]).as_bytes())?,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    writeln!(out, "\"}}")?;
+    Ok(())
+}
+
+/// the type of `object`. the manifest of a chunked file is a blob, as in tree entries.
+fn type_of(object: &Object) -> ObjectTypeLiteral {
+    match object {
+        Object::BlobObject(_) => ObjectTypeLiteral::Blob,
+        Object::TreeObject(_) => ObjectTypeLiteral::Tree,
+    }
+}
+
+/// the content of the tree with `oid` as it's stored, the NUL-terminated entries,
+/// which is not encoded again from the entries read.
+fn stored_tree_content(repo: &Repository, oid: &ObjectId) -> Result<Vec<u8>> {
+    let (_, content) = query::read_object_file(repo.store().as_ref(), oid)?;
+
+    Ok(content)
+}
+
+/// the size of the content of `object` with `oid`, as printed by `--batch`.
+fn size_of(repo: &Repository, oid: &ObjectId, object: &Object) -> Result<u64> {
+    Ok(match object {
+        Object::BlobObject(blob) => blob.size()?,
+        Object::TreeObject(_) => stored_tree_content(repo, oid)?.len() as u64,
+    })
+}
+
+/// an object of `--batch`, read and measured before anything of it is printed.
+enum BatchObject {
+    Blob(BlobObject, u64),
+    /// the content of the tree as it's stored.
+    Tree(Vec<u8>),
+}
+
+fn read_batch_object(repo: &Repository, oid: ObjectId) -> Result<BatchObject> {
+    Ok(match repo.read_object(oid)? {
+        Object::BlobObject(blob) => {
+            let size = blob.size()?;
+            BatchObject::Blob(blob, size)
+        }
+        Object::TreeObject(_) => BatchObject::Tree(stored_tree_content(repo, &oid)?),
+    })
+}
+
+/// `--batch` and `--batch-check`, see [`CatFileMode`].
+///
+/// each object is flushed once it's printed, so a script can feed oids one by one.
+/// an object which can't be read is printed as `{oid} corrupt`, with the error on stderr,
+/// and the objects after it are still printed.
+fn cat_batch(repo: &Repository, with_content: bool) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        // like Git, the rest of the line after the oid is ignored.
+        let name = line.split_whitespace().next().unwrap_or("");
+
+        let oid = name
+            .parse::<ObjectId>()
+            .ok()
+            .filter(|oid| oid.algorithm() == repo.hash_algorithm() && repo.has_object(oid));
+
+        match oid.map(|oid| (oid, read_batch_object(repo, oid))) {
+            Some((oid, Ok(object))) => {
+                match object {
+                    BatchObject::Blob(blob, size) => {
+                        writeln!(stdout, "{} {} {}", oid, ObjectTypeLiteral::Blob, size)?;
+                        if with_content {
+                            // the size is counted in a pass of its own, so the content
+                            // is streamed, and not held in memory.
+                            io::copy(&mut blob.origin_reader()?, &mut stdout)?;
+                        }
+                    }
+                    BatchObject::Tree(content) => {
+                        writeln!(
+                            stdout,
+                            "{} {} {}",
+                            oid,
+                            ObjectTypeLiteral::Tree,
+                            content.len()
+                        )?;
+                        if with_content {
+                            stdout.write_all(&content)?;
+                        }
+                    }
+                }
+                if with_content {
+                    writeln!(stdout)?;
+                }
+            }
+            Some((oid, Err(e))) => {
+                eprintln!("error: {}", e);
+                writeln!(stdout, "{} corrupt", oid)?;
+            }
+            None => writeln!(stdout, "{} missing", name)?,
+        }
+
+        stdout.flush()?;
+    }
+
+    Ok(())
+}
+
+/// This command is the "opposite" of hash-object: it can print an object by its `oid`.
+/// Its implementation just reads the file at `.ugit/objects/{oid}`.
+///
+/// `oid` is only [`None`] in batch mode, which reads the oids from stdin.
+///
+/// with `-e`, the exit code is [`ExitCode::FAILURE`] if the object doesn't exist.
+pub fn cmd_cat_file(
+    repo: &Repository,
+    oid: Option<ObjectId>,
+    mode: CatFileMode,
+    format: OutputFormat,
+) -> Result<ExitCode> {
+    let oid = match oid {
+        _ if mode.batch || mode.batch_check => {
+            return cat_batch(repo, mode.batch).map(|_| ExitCode::SUCCESS)
+        }
+        Some(oid) => oid,
+        None => return Err(Error::invalid_argument("the object is required")),
+    };
+
+    if mode.exists {
+        return match check_object(repo, &oid) {
+            Ok(()) => Ok(ExitCode::SUCCESS),
+            Err(Error::MissingObject(_)) => Ok(ExitCode::FAILURE),
+            Err(e) => Err(e),
+        };
+    }
+
+    check_object(repo, &oid)?;
+    let object = repo.read_object(oid)?;
+
+    if mode.show_type {
+        writeln!(io::stdout().lock(), "{}", type_of(&object))?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if mode.show_size {
+        writeln!(io::stdout().lock(), "{}", size_of(repo, &oid, &object)?)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(expected_type) = mode.expected_type {
+        if expected_type != type_of(&object) {
+            return Err(Error::TypeMismatch {
+                oid,
+                expected: expected_type,
+                actual: type_of(&object),
+            });
+        }
+    }
+
+    match object {
+        Object::BlobObject(blob) => {
+            match format {
+                // streamed, the content could be much larger than memory.
+                OutputFormat::Text => {
+                    io::copy(&mut blob.origin_reader()?, &mut io::stdout().lock())?;
+                }
+                OutputFormat::Json => print_json_blob(&oid, &blob)?,
+            }
+        }
+        Object::TreeObject(tree) => match format {
+            OutputFormat::Text if mode.pretty => {
+                let mut out = io::stdout().lock();
+                for entry in tree.entries()? {
+                    writeln!(out, "{}", entry)?;
+                }
+            }
+            OutputFormat::Text => io::stdout()
+                .lock()
+                .write_all(&stored_tree_content(repo, &oid)?)?,
+            OutputFormat::Json => output::print_json(&JsonCatFile::Tree {
+                oid: oid.to_string(),
+                entries: tree
+                    .entries()?
+                    .iter()
+                    .map(|entry| JsonTreeEntry::new(entry, Path::new("")))
+                    .collect(),
+            })?,
+        },
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a reader which yields one byte at a time, so every char is split.
+    struct ByteByByte<'a>(&'a [u8]);
+
+    impl Read for ByteByByte<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((&byte, rest)) if !buf.is_empty() => {
+                    buf[0] = byte;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    fn utf8_chunks(content: &[u8]) -> (bool, String) {
+        let mut text = String::new();
+        let is_utf8 = read_utf8_chunks(ByteByByte(content), |chunk| {
+            text.push_str(chunk);
+            Ok(())
+        })
+        .unwrap();
+
+        (is_utf8, text)
+    }
+
+    #[test]
+    fn chars_split_by_chunks_are_joined() {
+        let content = "é 😀 \"\n";
+        assert_eq!(utf8_chunks(content.as_bytes()), (true, content.to_owned()));
+    }
+
+    #[test]
+    fn bytes_which_are_not_utf8_stop_reading() {
+        assert!(!utf8_chunks(b"ab\xffcd").0);
+        // a char which is cut off at the end.
+        assert!(!utf8_chunks(&"é".as_bytes()[..1]).0);
+    }
+}
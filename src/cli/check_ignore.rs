@@ -0,0 +1,82 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use serde::Serialize;
+
+use ugit_rs::fs_tools::paths;
+use ugit_rs::{Repository, Result};
+
+use super::output::{self, json_path, JsonPath, OutputFormat};
+
+#[derive(Serialize)]
+struct JsonIgnorePattern {
+    source: JsonPath,
+    line_number: usize,
+    pattern: String,
+}
+
+#[derive(Serialize)]
+struct JsonCheckedPath {
+    path: JsonPath,
+    ignored: bool,
+    pattern: Option<JsonIgnorePattern>,
+}
+
+#[derive(Serialize)]
+struct JsonCheckIgnore {
+    paths: Vec<JsonCheckedPath>,
+}
+
+/// cmd of `check-ignore [-v] <PATHS>...`
+///
+/// prints each ignored path, and with `verbose` the pattern which ignores it:
+/// `{source}:{line number}:{pattern}\t{path}`. in verbose mode paths re-included by
+/// a `!pattern` are printed too, since that pattern explains why they are kept.
+/// in JSON, every path is printed, whether it's ignored or not.
+///
+/// the exit code is [`ExitCode::FAILURE`] if none of `paths` is ignored.
+pub fn cmd_check_ignore(
+    repo: &Repository,
+    paths: Vec<PathBuf>,
+    verbose: bool,
+    format: OutputFormat,
+) -> Result<ExitCode> {
+    let mut ignore_rules = repo.ignore_rules()?;
+    let mut any_ignored = false;
+    let mut checked = vec![];
+    let mut out = io::stdout().lock();
+
+    for path in paths {
+        let pattern = ignore_rules.explain(paths::normalize(&path)?)?;
+        let ignored = pattern.is_some_and(|pattern| !pattern.is_negated());
+        any_ignored |= ignored;
+
+        match (format, pattern) {
+            (OutputFormat::Json, pattern) => checked.push(JsonCheckedPath {
+                path: json_path(&path),
+                ignored,
+                pattern: pattern.map(|pattern| JsonIgnorePattern {
+                    source: json_path(pattern.source()),
+                    line_number: pattern.line_number(),
+                    pattern: pattern.origin_line().to_owned(),
+                }),
+            }),
+            (OutputFormat::Text, None) => {}
+            (OutputFormat::Text, Some(pattern)) if verbose => {
+                writeln!(out, "{}\t{}", pattern, path.display())?
+            }
+            (OutputFormat::Text, Some(_)) if ignored => writeln!(out, "{}", path.display())?,
+            (OutputFormat::Text, Some(_)) => {}
+        }
+    }
+
+    if format == OutputFormat::Json {
+        output::print_json(&JsonCheckIgnore { paths: checked })?;
+    }
+
+    match any_ignored {
+        true => Ok(ExitCode::SUCCESS),
+        false => Ok(ExitCode::FAILURE),
+    }
+}
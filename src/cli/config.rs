@@ -0,0 +1,270 @@
+use std::env;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args, Subcommand};
+use serde::Serialize;
+
+use ugit_rs::config::{self, Config, ConfigEntry, ConfigFile, ConfigScope};
+use ugit_rs::error::IoResultExt;
+use ugit_rs::fs_tools::dirs;
+use ugit_rs::{Error, Result};
+
+use super::output::{self, json_path, JsonPath, OutputFormat};
+use super::{locate_repository, Location};
+
+/// the scope options of `config`, at most one of them.
+#[derive(Args, Debug)]
+#[group(multiple = false)]
+pub struct ScopeArgs {
+    /// Use the system config file.
+    #[arg(long, global = true)]
+    system: bool,
+    /// Use the global config file of the user.
+    #[arg(long, global = true)]
+    global: bool,
+    /// Use the config file of the repository.
+    #[arg(long, global = true)]
+    local: bool,
+}
+
+impl ScopeArgs {
+    fn scope(&self) -> Option<ConfigScope> {
+        if self.system {
+            Some(ConfigScope::System)
+        } else if self.global {
+            Some(ConfigScope::Global)
+        } else if self.local {
+            Some(ConfigScope::Local)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the value of a variable, the one which takes precedence unless --all is given
+    Get {
+        /// Print all values of the variable.
+        #[arg(long)]
+        all: bool,
+        /// Also print the config file each value comes from.
+        #[arg(long = "show-origin")]
+        show_origin: bool,
+        key: String,
+    },
+
+    /// Set a variable, in the config file of the repository unless a scope is given
+    Set {
+        /// Add a value to the variable, instead of replacing its value.
+        #[arg(long)]
+        add: bool,
+        key: String,
+        #[arg(allow_hyphen_values = true)]
+        value: String,
+    },
+
+    /// Remove all values of a variable
+    Unset { key: String },
+
+    /// List all variables
+    List {
+        /// Also print the config file each variable comes from.
+        #[arg(long = "show-origin")]
+        show_origin: bool,
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+}
+
+#[derive(Serialize)]
+struct JsonConfigEntry<'a> {
+    key: &'a str,
+    value: Option<&'a str>,
+    scope: String,
+    origin: JsonPath,
+}
+
+#[derive(Serialize)]
+struct JsonConfigList<'a> {
+    entries: Vec<JsonConfigEntry<'a>>,
+}
+
+/// the config file of `scope`.
+fn scope_path(scope: ConfigScope, ugit_dir: Option<&PathBuf>) -> Result<PathBuf> {
+    match scope.path(ugit_dir.map(PathBuf::as_path)) {
+        Some(path) => Ok(path),
+        None if scope == ConfigScope::Local => Err(Error::NotARepository {
+            path: env::current_dir().with_path(".")?,
+            searched_parents: true,
+        }),
+        None => Err(Error::invalid_argument(format!(
+            "there is no {} config file",
+            scope
+        ))),
+    }
+}
+
+/// the entry of the object format in `file`, [`None`] if it's not set.
+fn object_format_entry(file: &ConfigFile) -> Option<ConfigEntry> {
+    let key = config::canonical_key(config::OBJECT_FORMAT_KEY).ok()?;
+
+    file.entries()
+        .filter(|entry| entry.key() == key)
+        .last()
+        .cloned()
+}
+
+/// edit the config file `file` of `scope` with `edit`.
+///
+/// fails with [`Error::InvalidConfig`] if `edit` changes the object format of the
+/// repository at `ugit_dir` once it has objects, which could not be read with another
+/// hash algorithm. the object format is only read from the local scope.
+fn edit_scope<F, T>(
+    file: &mut ConfigFile,
+    scope: ConfigScope,
+    ugit_dir: Option<&Path>,
+    edit: F,
+) -> Result<T>
+where
+    F: FnOnce(&mut ConfigFile) -> Result<T>,
+{
+    let ugit_dir = match (scope, ugit_dir) {
+        (ConfigScope::Local, Some(ugit_dir)) => ugit_dir,
+        _ => return edit(file),
+    };
+
+    let origin = object_format_entry(file);
+    let result = edit(file)?;
+    let edited = object_format_entry(file);
+
+    let value =
+        |entry: &Option<ConfigEntry>| entry.as_ref().map(|entry| entry.value().map(str::to_owned));
+    if value(&edited) == value(&origin) || !dirs::has_objects(ugit_dir)? {
+        return Ok(result);
+    }
+
+    let entry = edited.or(origin).expect("one of the object formats is set");
+    Err(entry.invalid(format!(
+        "can't change '{}' of a repository which has objects",
+        config::OBJECT_FORMAT_KEY
+    )))
+}
+
+/// `file:{source}\t`, the origin of `entry` for `--show-origin`.
+fn origin(entry: &ConfigEntry, show_origin: bool) -> String {
+    match show_origin {
+        true => format!("file:{}\t", entry.source().display()),
+        false => String::new(),
+    }
+}
+
+/// cmd of `config [--system | --global | --local] <get | set | unset | list>`
+///
+/// reads all scopes unless one is given, and writes the config file of the repository
+/// unless one is given. the exit code is [`ExitCode::FAILURE`] if the variable to get or
+/// unset is not set.
+pub fn cmd_config(
+    location: &Location,
+    scope: ScopeArgs,
+    command: ConfigCommand,
+) -> Result<ExitCode> {
+    let ugit_dir = locate_repository(location)?.map(|(ugit_dir, _)| ugit_dir);
+    let scope = scope.scope();
+
+    let load = || match scope {
+        Some(scope) => {
+            let path = scope_path(scope, ugit_dir.as_ref())?;
+            Ok(Config::from_iter(
+                ConfigFile::read(path, scope)?.entries().cloned(),
+            ))
+        }
+        None => Config::load(ugit_dir.as_deref()),
+    };
+
+    match command {
+        ConfigCommand::Get {
+            all,
+            show_origin,
+            key,
+        } => {
+            config::canonical_key(&key)?;
+            let config = load()?;
+
+            let mut entries: Vec<_> = config.get_all(&key).collect();
+            if !all {
+                entries = entries.split_off(entries.len().saturating_sub(1));
+            }
+            if entries.is_empty() {
+                return Ok(ExitCode::FAILURE);
+            }
+
+            for entry in entries {
+                writeln!(
+                    io::stdout().lock(),
+                    "{}{}",
+                    origin(entry, show_origin),
+                    entry.value().unwrap_or("")
+                )?;
+            }
+        }
+        ConfigCommand::Set { add, key, value } => {
+            let scope = scope.unwrap_or(ConfigScope::Local);
+            let path = scope_path(scope, ugit_dir.as_ref())?;
+
+            ConfigFile::edit(path, scope, |file| {
+                edit_scope(file, scope, ugit_dir.as_deref(), |file| match add {
+                    true => file.add(&key, &value),
+                    false => file.set(&key, &value),
+                })
+            })?;
+        }
+        ConfigCommand::Unset { key } => {
+            let scope = scope.unwrap_or(ConfigScope::Local);
+            let path = scope_path(scope, ugit_dir.as_ref())?;
+
+            let removed = ConfigFile::edit(path, scope, |file| {
+                edit_scope(file, scope, ugit_dir.as_deref(), |file| file.unset(&key))
+            })?;
+            if removed == 0 {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        ConfigCommand::List {
+            show_origin,
+            format: OutputFormat::Text,
+        } => {
+            for entry in load()?.entries() {
+                writeln!(
+                    io::stdout().lock(),
+                    "{}{}",
+                    origin(entry, show_origin),
+                    entry
+                )?;
+            }
+        }
+        ConfigCommand::List {
+            format: OutputFormat::Json,
+            ..
+        } => {
+            let config = load()?;
+            let entries = config
+                .entries()
+                .iter()
+                .map(|entry| JsonConfigEntry {
+                    key: entry.key(),
+                    value: entry.value(),
+                    scope: entry.scope().to_string(),
+                    origin: json_path(entry.source()),
+                })
+                .collect();
+
+            output::print_json(&JsonConfigList { entries })?;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
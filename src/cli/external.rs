@@ -0,0 +1,146 @@
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+
+use ugit_rs::error::IoResultExt;
+use ugit_rs::{Error, Result};
+
+use super::{locate_repository, Location, UGIT_DIR_ENV, UGIT_WORK_TREE_ENV};
+
+/// environment variable set for external commands and shell aliases: the path of cwd
+/// relative to the work tree root, with a trailing `/`. it's empty at the root.
+pub const UGIT_PREFIX_ENV: &str = "UGIT_PREFIX";
+
+/// the prefix of the executables which extend ugit-rs, `ugit-rs-<name>`.
+const EXTERNAL_COMMAND_PREFIX: &str = "ugit-rs-";
+
+/// the environment variables which tell a child process where the repository is,
+/// none outside a repository. the child runs in cwd, so relative paths given to it
+/// still resolve, and finds the work tree root with `{UGIT_PREFIX_ENV}`.
+pub fn repository_env(location: &Location) -> Result<Vec<(&'static str, OsString)>> {
+    let (ugit_dir, work_tree) = match locate_repository(location)? {
+        Some(located) => located,
+        None => return Ok(vec![]),
+    };
+
+    let cwd = env::current_dir().with_path(".")?;
+    let prefix = match cwd.strip_prefix(&work_tree) {
+        Ok(prefix) if !prefix.as_os_str().is_empty() => {
+            let mut prefix = prefix.as_os_str().to_owned();
+            prefix.push("/");
+            prefix
+        }
+        _ => OsString::new(),
+    };
+
+    Ok(vec![
+        (UGIT_DIR_ENV, ugit_dir.into_os_string()),
+        (UGIT_WORK_TREE_ENV, work_tree.into_os_string()),
+        (UGIT_PREFIX_ENV, prefix),
+    ])
+}
+
+/// the executable `{EXTERNAL_COMMAND_PREFIX}{name}` in one of the dirs of `search_path`,
+/// which is like `$PATH`. the first one wins.
+fn find_external_command(name: &OsStr, search_path: &OsStr) -> Option<PathBuf> {
+    if name.is_empty() || name.to_string_lossy().contains('/') {
+        return None;
+    }
+
+    let mut file_name = OsString::from(EXTERNAL_COMMAND_PREFIX);
+    file_name.push(name);
+
+    env::split_paths(search_path)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| {
+            path.metadata().is_ok_and(|metadata| {
+                metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+            })
+        })
+}
+
+/// replace this process with the external command `args[0]`, found in `$PATH` by
+/// [`find_external_command`], which is given the rest of `args` and the [`repository_env`].
+/// so ugit-rs exits with its exit code.
+///
+/// only returns if it fails, with [`Error::InvalidArgument`] if there is no such command.
+pub fn exec(location: &Location, args: &[OsString]) -> Result<()> {
+    let name = &args[0];
+    let search_path = env::var_os("PATH").unwrap_or_default();
+    let path = match find_external_command(name, &search_path) {
+        Some(path) => path,
+        None => {
+            return Err(Error::invalid_argument(format!(
+                "'{}' is not a ugit-rs command",
+                name.to_string_lossy()
+            )))
+        }
+    };
+
+    let e = Command::new(&path)
+        .args(&args[1..])
+        .envs(repository_env(location)?)
+        .exec();
+
+    Err(e).with_path(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// a new empty dir in the temp dir.
+    fn tmp_dir() -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = env::temp_dir().join(format!(
+            "ugit-rs-external-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    fn write_script(path: &Path, mode: u32) {
+        fs::write(path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn external_commands_are_found_in_the_search_path() {
+        let root = tmp_dir();
+        let (first, second) = (root.join("first"), root.join("second"));
+        fs::create_dir_all(&first).unwrap();
+        fs::create_dir_all(&second).unwrap();
+        write_script(&first.join("ugit-rs-not-executable"), 0o644);
+        write_script(&second.join("ugit-rs-not-executable"), 0o755);
+        write_script(&first.join("ugit-rs-both"), 0o755);
+        write_script(&second.join("ugit-rs-both"), 0o755);
+        fs::create_dir(first.join("ugit-rs-dir")).unwrap();
+
+        let search_path = env::join_paths([&first, &second]).unwrap();
+        let find = |name: &str| find_external_command(OsStr::new(name), &search_path);
+
+        assert_eq!(find("both"), Some(first.join("ugit-rs-both")));
+        assert_eq!(
+            find("not-executable"),
+            Some(second.join("ugit-rs-not-executable"))
+        );
+        assert_eq!(find("dir"), None);
+        assert_eq!(find("missing"), None);
+        assert_eq!(find(""), None);
+        assert_eq!(find("../first/ugit-rs-both"), None);
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
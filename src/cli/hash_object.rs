@@ -0,0 +1,153 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use ugit_rs::crypto::ObjectId;
+use ugit_rs::fs_tools::dirs;
+use ugit_rs::fs_tools::paths;
+use ugit_rs::fs_tools::pathspec::Pathspec;
+use ugit_rs::objects::db::insert::ObjectInsert;
+use ugit_rs::objects::type_literal::ObjectTypeLiteral;
+use ugit_rs::{Error, Repository, Result};
+
+use super::output::{self, json_path, JsonPath, OutputFormat};
+
+#[derive(Serialize)]
+struct JsonHashedObject {
+    path: JsonPath,
+    #[serde(rename = "type")]
+    obj_type: String,
+    oid: String,
+}
+
+#[derive(Serialize)]
+struct JsonHashObject {
+    objects: Vec<JsonHashedObject>,
+}
+
+/// file(or dir) with given path -> Object
+/// and the object will stored in database.
+///
+/// 1. Get the path of the file to store.
+/// 2. Read the file.
+/// 3. Hash the content of the file with the object format of the repository
+///    (`extensions.objectFormat`, SHA-1 by default).
+/// 4. Store the file under ".ugit/objects/{the hex oid}".
+///
+/// for blobs, a path to a file is hashed as it is, even if it's ignored or outside the
+/// work tree. the other paths are a pathspec selecting the files of the work tree to hash,
+/// whose oids are printed where the first of them is given. one oid is printed for each file.
+///
+/// for trees, the dirs must be in the work tree.
+pub fn cmd_hash_object(
+    repo: &Repository,
+    pathspec: Vec<PathBuf>,
+    obj_type: ObjectTypeLiteral,
+    format: OutputFormat,
+) -> Result<()> {
+    let mut hashed: Vec<(PathBuf, ObjectId)> = vec![];
+    let mut out = io::stdout().lock();
+    let mut print = |path: PathBuf, oid: ObjectId| -> io::Result<()> {
+        match format {
+            OutputFormat::Text => writeln!(out, "{}", oid)?,
+            OutputFormat::Json => hashed.push((path, oid)),
+        }
+        Ok(())
+    };
+
+    match obj_type {
+        ObjectTypeLiteral::Blob => {
+            let specs: Vec<_> = pathspec.iter().filter(|path| !path.is_file()).collect();
+
+            let mut selected_files = match specs.is_empty() {
+                true => None,
+                false => {
+                    let pathspec = Pathspec::new(&specs, repo.work_tree())?;
+                    let mut skipped = vec![];
+                    let selected_files = repo
+                        .build_tree(repo.work_tree(), &pathspec, &mut skipped)?
+                        .files();
+                    output::warn_skipped(&skipped);
+
+                    if selected_files.is_empty() {
+                        return Err(Error::invalid_argument(
+                            "the pathspec did not match any files",
+                        ));
+                    }
+
+                    Some(selected_files)
+                }
+            };
+
+            for path in pathspec {
+                if path.is_file() {
+                    // hashed and stored in a single streaming pass.
+                    let oid = repo.write_file(&path)?;
+                    print(work_tree_path(repo, &paths::normalize(&path)?), oid)?;
+                } else {
+                    for (path, _, blob) in selected_files.take().into_iter().flatten() {
+                        print(path, blob.insert_into_db()?)?;
+                    }
+                }
+            }
+        }
+
+        ObjectTypeLiteral::Tree => {
+            for path in pathspec {
+                // must be dir.
+                let dir = paths::normalize(&path)?;
+                if !dirs::is_dir_exist(&dir) {
+                    return Err(Error::invalid_argument(format!(
+                        "the dir path {} is wrong",
+                        path.display()
+                    )));
+                }
+                // the ignore rules and the pathspec only apply in the work tree.
+                if !dir.starts_with(repo.work_tree()) {
+                    return Err(Error::invalid_argument(format!(
+                        "'{}' is outside repository",
+                        path.display()
+                    )));
+                }
+
+                let mut skipped = vec![];
+                let tree =
+                    repo.build_tree(&dir, &Pathspec::all(repo.work_tree())?, &mut skipped)?;
+                output::warn_skipped(&skipped);
+                print(work_tree_path(repo, &dir), tree.insert_into_db()?)?;
+            }
+        }
+
+        ObjectTypeLiteral::Manifest => {
+            return Err(Error::invalid_argument(
+                "manifest objects are only created by chunking large files",
+            ));
+        }
+    }
+
+    if format == OutputFormat::Json {
+        let objects = hashed
+            .iter()
+            .map(|(path, oid)| JsonHashedObject {
+                path: json_path(path),
+                obj_type: obj_type.to_string(),
+                oid: oid.to_string(),
+            })
+            .collect();
+
+        output::print_json(&JsonHashObject { objects })?;
+    }
+
+    Ok(())
+}
+
+/// `path` relative to the work tree root, like the paths of the other commands,
+/// or as it is if it's outside the work tree.
+fn work_tree_path(repo: &Repository, path: &Path) -> PathBuf {
+    match path.strip_prefix(repo.work_tree()) {
+        Ok(path) if path.as_os_str().is_empty() => PathBuf::from("."),
+        Ok(path) => path.to_path_buf(),
+        Err(_) => path.to_path_buf(),
+    }
+}
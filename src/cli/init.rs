@@ -1,34 +1,55 @@
-use std::{env, io};
+use std::env;
+use std::io::{self, Write};
 
-use crate::fs_tools::dirs;
+use ugit_rs::config::{Config, DEFAULT_OBJECT_FORMAT_KEY};
+use ugit_rs::crypto::HashAlgorithm;
+use ugit_rs::error::IoResultExt;
+use ugit_rs::fs_tools::dirs::UGIT_REPOSITORY_NAME;
+use ugit_rs::{Error, InitOptions, Repository, Result};
 
-pub fn cmd_init() {
-    match dirs::create_repo_dir() {
-        Ok(_) => {}
-        Err(e) => {
-            if e.kind() == io::ErrorKind::AlreadyExists {
-                eprintln!("this repo has initialized.");
-                return;
-            } else {
-                eprintln!("init error: {}", e);
-                return;
-            }
-        }
-    }
+use super::Location;
 
-    match dirs::create_objects_dir() {
-        Ok(_) => {}
-        Err(e) => {
-            eprintln!("init objects dir error: {}", e);
-            return;
-        }
-    }
+/// create the repository dir, `{cwd}/{UGIT_REPOSITORY_NAME}` unless `--ugit-dir` is given.
+///
+/// without `object_format`, it's `{DEFAULT_OBJECT_FORMAT_KEY}` of the global config, or SHA-1.
+/// initializing an existing repository again is not an error, it's left as it is.
+pub fn cmd_init(
+    location: &Location,
+    object_format: Option<HashAlgorithm>,
+    chunked_blobs: bool,
+) -> Result<()> {
+    let cwd = env::current_dir().with_path(".")?;
+    let ugit_dir = location
+        .ugit_dir
+        .clone()
+        .unwrap_or_else(|| cwd.join(UGIT_REPOSITORY_NAME));
+    let work_tree = location.work_tree.clone().unwrap_or(cwd);
 
-    let cwd = env::current_dir().unwrap();
+    let object_format = match object_format {
+        Some(object_format) => object_format,
+        None => Config::load(None)?
+            .get_object_format(DEFAULT_OBJECT_FORMAT_KEY)?
+            .unwrap_or_default(),
+    };
 
-    println!(
-        "Initialized empty ugit repository in {}/{}",
-        cwd.to_str().unwrap(),
-        dirs::UGIT_REPOSITORY_NAME
-    );
+    let options = InitOptions {
+        object_format,
+        chunked_blobs,
+    };
+
+    match Repository::init(ugit_dir, work_tree, options) {
+        Ok(repo) => {
+            writeln!(
+                io::stdout().lock(),
+                "Initialized empty ugit repository in {}",
+                repo.ugit_dir().display()
+            )?;
+            Ok(())
+        }
+        Err(Error::Io { source, .. }) if source.kind() == io::ErrorKind::AlreadyExists => {
+            eprintln!("this repo has initialized.");
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
 }
@@ -0,0 +1,148 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use ugit_rs::crypto::ObjectId;
+use ugit_rs::fs_tools::pathspec::Pathspec;
+use ugit_rs::objects::type_literal::ObjectTypeLiteral;
+use ugit_rs::objects::Object;
+use ugit_rs::{Repository, Result};
+
+use super::check_object;
+use super::output::{self, JsonTreeEntry, OutputFormat};
+
+/// how `ls-tree` lists the entries.
+#[derive(Debug, Clone, Copy)]
+pub struct LsTreeOptions {
+    /// list the entries of subtrees instead of the subtrees.
+    pub recursive: bool,
+    /// only list trees.
+    pub dir_only: bool,
+    pub name_only: bool,
+    /// also list the size of blobs.
+    pub long: bool,
+    pub format: OutputFormat,
+}
+
+#[derive(Serialize)]
+struct JsonListedEntry {
+    #[serde(flatten)]
+    entry: JsonTreeEntry,
+    size: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct JsonLsTree {
+    entries: Vec<JsonListedEntry>,
+}
+
+/// list the entries of the tree with `tree_oid`, whose path relative to the listed tree
+/// is `dir`.
+///
+/// a subtree is walked into if it's selected by `pathspec` and the listing is recursive,
+/// or if it's not selected itself but some of its entries may be. only the entries of the
+/// trees walked into are read, and blobs only for their size with `-l`.
+fn list(
+    repo: &Repository,
+    tree_oid: ObjectId,
+    dir: &Path,
+    pathspec: &Pathspec,
+    options: LsTreeOptions,
+    json_entries: &mut Vec<JsonListedEntry>,
+) -> Result<()> {
+    for entry in repo.read_tree_entries(tree_oid)? {
+        let path = dir.join(entry.origin_file_name());
+        let absolute_path = repo.work_tree().join(&path);
+        let is_tree = entry.obj_type() == ObjectTypeLiteral::Tree;
+        let selected = pathspec.matches(&absolute_path);
+
+        let listed = selected
+            && match is_tree {
+                true => options.dir_only || !options.recursive,
+                false => !options.dir_only,
+            };
+        if listed {
+            let size = match options.long && !is_tree {
+                true => match repo.read_object(entry.oid())? {
+                    Object::BlobObject(blob) => Some(blob.size()?),
+                    Object::TreeObject(_) => None,
+                },
+                false => None,
+            };
+
+            match options.format {
+                OutputFormat::Text if options.name_only => {
+                    writeln!(io::stdout().lock(), "{}", path.display())?
+                }
+                OutputFormat::Text if options.long => writeln!(
+                    io::stdout().lock(),
+                    "{} {} {} {:>7}\t{}",
+                    entry.mode(),
+                    entry.obj_type(),
+                    entry.oid(),
+                    size.map_or("-".to_owned(), |size| size.to_string()),
+                    path.display()
+                )?,
+                OutputFormat::Text => writeln!(
+                    io::stdout().lock(),
+                    "{} {} {}\t{}",
+                    entry.mode(),
+                    entry.obj_type(),
+                    entry.oid(),
+                    path.display()
+                )?,
+                OutputFormat::Json => json_entries.push(JsonListedEntry {
+                    entry: JsonTreeEntry::new(&entry, dir),
+                    size,
+                }),
+            }
+        }
+
+        if is_tree {
+            let walked_into = match selected {
+                true => options.recursive,
+                false => pathspec.may_match_under(&absolute_path),
+            };
+            if walked_into {
+                list(repo, entry.oid(), &path, pathspec, options, json_entries)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// cmd of `ls-tree [-r] [-d] [--name-only] [-l] <TREE> [PATHS]...`
+///
+/// prints `{mode} {type} {oid}\t{path}` for each entry of the tree, or with `-l`
+/// `{mode} {type} {oid} {size}\t{path}`, where the size of a tree is `-`.
+/// paths are relative to the tree, and `paths` select entries like a pathspec.
+pub fn cmd_ls_tree(
+    repo: &Repository,
+    oid: ObjectId,
+    paths: Vec<PathBuf>,
+    options: LsTreeOptions,
+) -> Result<()> {
+    check_object(repo, &oid)?;
+
+    let pathspec = Pathspec::new(&paths, repo.work_tree())?;
+
+    let mut json_entries = vec![];
+    list(
+        repo,
+        oid,
+        Path::new(""),
+        &pathspec,
+        options,
+        &mut json_entries,
+    )?;
+
+    if options.format == OutputFormat::Json {
+        output::print_json(&JsonLsTree {
+            entries: json_entries,
+        })?;
+    }
+
+    Ok(())
+}
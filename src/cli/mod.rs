@@ -1,17 +1,55 @@
-use std::path::{Path, PathBuf};
+use std::env;
+use std::ffi::OsString;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
 
 use clap::{Parser, Subcommand};
 
-use crate::objects;
-use crate::objects::type_literal::ObjectTypeLiteral;
+use ugit_rs::crypto::{HashAlgorithm, ObjectId};
+use ugit_rs::error::IoResultExt;
+use ugit_rs::fs_tools::dirs::{self, UGIT_REPOSITORY_NAME};
+use ugit_rs::fs_tools::paths;
+use ugit_rs::objects::type_literal::ObjectTypeLiteral;
+use ugit_rs::{Error, Repository, Result};
+
+use output::OutputFormat;
 
 pub mod add;
+pub mod alias;
+pub mod cat_file;
+pub mod check_ignore;
+pub mod config;
+pub mod external;
+pub mod hash_object;
 pub mod init;
+pub mod ls_tree;
+pub mod output;
+pub mod read_tree;
+pub mod write_tree;
+
+/// environment variable overriding where the repository dir is, like `--ugit-dir`.
+pub const UGIT_DIR_ENV: &str = "UGIT_DIR";
+/// environment variable overriding where the work tree root is, like `--work-tree`.
+pub const UGIT_WORK_TREE_ENV: &str = "UGIT_WORK_TREE";
 
 /// Simple program to learn more about how Git works on the inside.
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
+    /// Run as if ugit-rs was started in <path> instead of the current working directory.
+    /// When multiple -C options are given, each is interpreted relative to the previous one.
+    #[arg(short = 'C', value_name = "path")]
+    pub change_dirs: Vec<PathBuf>,
+
+    /// Set the path to the repository (".ugit-rs" directory).
+    #[arg(long = "ugit-dir", value_name = "path", env = UGIT_DIR_ENV)]
+    pub ugit_dir: Option<PathBuf>,
+
+    /// Set the path to the root of the working tree.
+    #[arg(long = "work-tree", value_name = "path", env = UGIT_WORK_TREE_ENV)]
+    pub work_tree: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -19,7 +57,15 @@ pub struct Cli {
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Create an empty Git repository or reinitialize an existing one
-    Init {},
+    Init {
+        /// Specify the hash algorithm used to compute object ids.
+        /// Defaults to init.defaultObjectFormat of the config, or sha1.
+        #[arg(long = "object-format", value_enum)]
+        object_format: Option<HashAlgorithm>,
+        /// Store large files as content-defined chunks, so small edits only store the changed chunks.
+        #[arg(long = "chunked-blobs")]
+        chunked_blobs: bool,
+    },
 
     /// Add file contents to the index
     Add {
@@ -31,42 +77,438 @@ pub enum Commands {
         /// Note that older versions of Git used to ignore removed files; use --no-all option if you
         /// want to add modified or new files but ignore removed ones.
         pathspec: Vec<PathBuf>,
+        /// Add every file in the work tree, matching the pathspec if one is given.
+        /// Without a pathspec `add` adds nothing unless this is given.
+        #[arg(short = 'A', long)]
+        all: bool,
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
     },
 
     /// Compute object ID and optionally create an object from a file
     HashObject {
-        path: PathBuf,
+        /// Files to hash, or a pathspec of files in the work tree, see `add`. for `-t tree`,
+        /// the dirs to hash.
+        #[arg(required = true)]
+        pathspec: Vec<PathBuf>,
         /// Specify the type of object to be created.
         #[arg(short = 't', long = "type", value_enum, default_value_t = ObjectTypeLiteral::Blob)]
         obj_type: ObjectTypeLiteral,
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
     },
 
     /// Provide content or type and size information for repository objects.
     CatFile {
-        oid: String,
-        #[arg(short = 't', long = "type", value_enum, default_value_t = ObjectTypeLiteral::Blob)]
-        expected_type: ObjectTypeLiteral,
+        #[arg(
+            required_unless_present_any = ["batch", "batch_check"],
+            conflicts_with_all = ["batch", "batch_check"]
+        )]
+        oid: Option<ObjectId>,
+        #[command(flatten)]
+        mode: cat_file::CatFileMode,
+        /// Output format of the content.
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = OutputFormat::Text,
+            conflicts_with_all = ["show_type", "show_size", "exists", "batch", "batch_check"]
+        )]
+        format: OutputFormat,
     },
 
     /// Create a tree object from the current index.
-    WriteTree {},
+    WriteTree {
+        /// Only write the files matching the pathspec.
+        pathspec: Vec<PathBuf>,
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
 
     /// Reads tree information into the index.
-    ReadTree { oid: String },
+    ReadTree {
+        oid: ObjectId,
+        /// Only restore the files matching the pathspec, leaving other files alone.
+        pathspec: Vec<PathBuf>,
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+
+    /// List the contents of a tree object
+    LsTree {
+        /// Recurse into subtrees, listing their entries instead of them.
+        #[arg(short = 'r')]
+        recursive: bool,
+        /// Only list trees. With -r, the subtrees are listed as they are recursed into.
+        #[arg(short = 'd')]
+        dir_only: bool,
+        /// Only list the paths of the entries.
+        #[arg(long = "name-only")]
+        name_only: bool,
+        /// Also list the size of blobs.
+        #[arg(short = 'l', long = "long", conflicts_with = "name_only")]
+        long: bool,
+        /// The tree to list.
+        oid: ObjectId,
+        /// Only list the entries matching these paths, recursing into the trees they are in.
+        paths: Vec<PathBuf>,
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+
+    /// Debug ugitignore / exclude files
+    CheckIgnore {
+        /// Also output the ignore file, line number and pattern matching each path.
+        #[arg(short = 'v', long = "verbose")]
+        verbose: bool,
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Output format.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+    },
+
+    /// Get and set repository or global options
+    Config {
+        #[command(flatten)]
+        scope: config::ScopeArgs,
+        #[command(subcommand)]
+        command: config::ConfigCommand,
+    },
+
+    /// A command which is not built in: an alias, see `alias.<name>` of the config,
+    /// or an executable `ugit-rs-<name>` on PATH.
+    #[command(external_subcommand)]
+    External(Vec<OsString>),
 }
 
-pub fn run() {
-    let cli = Cli::parse();
+/// where the repository is, from the global options.
+pub struct Location {
+    /// `--ugit-dir`, absolute.
+    pub ugit_dir: Option<PathBuf>,
+    /// `--work-tree`, absolute.
+    pub work_tree: Option<PathBuf>,
+}
+
+/// apply the global options, before anything in the repository is accessed.
+fn setup_location(cli: &Cli) -> Result<Location> {
+    for dir in &cli.change_dirs {
+        env::set_current_dir(dir).with_path(dir)?;
+    }
+
+    // relative to the dir given by `-C`.
+    Ok(Location {
+        ugit_dir: cli.ugit_dir.as_ref().map(paths::normalize).transpose()?,
+        work_tree: cli.work_tree.as_ref().map(paths::normalize).transpose()?,
+    })
+}
+
+/// the repository dir and the work tree root of `location`, [`None`] outside a repository.
+///
+/// with `--ugit-dir` it's that repository dir, whose work tree is cwd unless
+/// `--work-tree` is given. otherwise it's discovered from cwd up.
+///
+/// the repository is not opened, which would read the config files of all scopes,
+/// so `config` can edit one of them while another can't be parsed.
+fn locate_repository(location: &Location) -> Result<Option<(PathBuf, PathBuf)>> {
+    let cwd = env::current_dir().with_path(".")?;
+
+    Ok(locate_repository_in(location, cwd))
+}
+
+/// [`locate_repository`] from `cwd`.
+fn locate_repository_in(location: &Location, cwd: PathBuf) -> Option<(PathBuf, PathBuf)> {
+    let (ugit_dir, default_work_tree) = match &location.ugit_dir {
+        Some(ugit_dir) if dirs::is_repo_dir(ugit_dir) => (ugit_dir.clone(), cwd),
+        Some(_) => return None,
+        None => {
+            let work_tree = dirs::discover_work_tree_root(&cwd)?;
+            (work_tree.join(UGIT_REPOSITORY_NAME), work_tree)
+        }
+    };
+
+    let work_tree = location.work_tree.clone().unwrap_or(default_work_tree);
+    Some((ugit_dir, work_tree))
+}
 
-    match cli.command {
-        Some(Commands::Init {}) => init::cmd_init(),
-        Some(Commands::Add { pathspec }) => add::cmd_add(pathspec),
-        Some(Commands::HashObject { path, obj_type }) => objects::cmd_hash_object(path, obj_type),
-        Some(Commands::CatFile { oid, expected_type }) => objects::cmd_cat_file(oid, expected_type),
-        Some(Commands::WriteTree {}) => objects::cmd_write_tree(Path::new(".")),
-        Some(Commands::ReadTree { oid }) => objects::cmd_read_tree(oid),
+/// the repository the command works on, see [`locate_repository`].
+fn open_repository(location: &Location) -> Result<Repository> {
+    let (ugit_dir, work_tree) = match locate_repository(location)? {
+        Some(located) => located,
+        None => {
+            return Err(match &location.ugit_dir {
+                Some(ugit_dir) => Error::NotARepository {
+                    path: ugit_dir.clone(),
+                    searched_parents: false,
+                },
+                None => Error::NotARepository {
+                    path: env::current_dir().with_path(".")?,
+                    searched_parents: true,
+                },
+            })
+        }
+    };
+
+    if !dirs::is_dir_exist(&work_tree) {
+        return Err(Error::invalid_argument(format!(
+            "the work tree '{}' is not a dir",
+            work_tree.display()
+        )));
+    }
+
+    Repository::open(ugit_dir, work_tree)
+}
+
+/// the given oid must have been computed by the hash algorithm of the repository,
+/// and the object must exist in it.
+fn check_object(repo: &Repository, oid: &ObjectId) -> Result<()> {
+    if oid.algorithm() != repo.hash_algorithm() {
+        return Err(Error::invalid_argument(format!(
+            "the object id {} is a {} id, but the repository uses {}",
+            oid,
+            oid.algorithm(),
+            repo.hash_algorithm()
+        )));
+    }
+
+    if !repo.has_object(oid) {
+        return Err(Error::MissingObject(*oid));
+    }
+
+    Ok(())
+}
+
+/// the exit code of a command which failed with `e`.
+///
+/// `1` is left to commands which report a negative result, like `check-ignore`,
+/// and `2` is what clap exits with for a wrong usage.
+fn exit_code(e: &Error) -> u8 {
+    match e {
+        Error::InvalidArgument(_) => 2,
+        Error::NotARepository { .. } => 3,
+        Error::MissingObject(_) => 4,
+        Error::CorruptObject { .. } => 5,
+        Error::TypeMismatch { .. } => 6,
+        Error::Io { .. } => 7,
+        Error::Locked { .. } => 8,
+        Error::InvalidConfig { .. } => 9,
+    }
+}
+
+/// run the command of `cli`. a command which reports a negative result, like `cat-file -e`
+/// of a missing object, returns [`ExitCode::FAILURE`] rather than exiting itself.
+fn run_command(cli: Cli) -> Result<ExitCode> {
+    let location = setup_location(&cli)?;
+    let (location, command) = alias::expand(location, cli.command)?;
+
+    match command {
+        Some(Commands::Init {
+            object_format,
+            chunked_blobs,
+        }) => init::cmd_init(&location, object_format, chunked_blobs),
+        Some(Commands::Add {
+            pathspec,
+            all,
+            format,
+        }) => add::cmd_add(&open_repository(&location)?, pathspec, all, format),
+        Some(Commands::HashObject {
+            pathspec,
+            obj_type,
+            format,
+        }) => {
+            hash_object::cmd_hash_object(&open_repository(&location)?, pathspec, obj_type, format)
+        }
+        Some(Commands::CatFile { oid, mode, format }) => {
+            return cat_file::cmd_cat_file(&open_repository(&location)?, oid, mode, format)
+        }
+        Some(Commands::WriteTree { pathspec, format }) => {
+            write_tree::cmd_write_tree(&open_repository(&location)?, pathspec, format)
+        }
+        Some(Commands::ReadTree {
+            oid,
+            pathspec,
+            format,
+        }) => read_tree::cmd_read_tree(&open_repository(&location)?, oid, pathspec, format),
+        Some(Commands::LsTree {
+            recursive,
+            dir_only,
+            name_only,
+            long,
+            oid,
+            paths,
+            format,
+        }) => {
+            let options = ls_tree::LsTreeOptions {
+                recursive,
+                dir_only,
+                name_only,
+                long,
+                format,
+            };
+            ls_tree::cmd_ls_tree(&open_repository(&location)?, oid, paths, options)
+        }
+        Some(Commands::CheckIgnore {
+            verbose,
+            paths,
+            format,
+        }) => {
+            return check_ignore::cmd_check_ignore(
+                &open_repository(&location)?,
+                paths,
+                verbose,
+                format,
+            )
+        }
+        Some(Commands::Config { scope, command }) => {
+            return config::cmd_config(&location, scope, command)
+        }
+        Some(Commands::External(args)) => external::exec(&location, &args),
         None => {
             // TODO: print help msg
+            Ok(())
         }
+    }?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+/// the exit code of a command whose output was closed early, like `ugit-rs ls-tree -r | head`,
+/// which is what a shell reports for a process killed by `SIGPIPE`.
+const BROKEN_PIPE_EXIT_CODE: u8 = 141;
+
+/// run the command line, the exit code is [`exit_code`] if the command fails.
+///
+/// a command whose stdout is closed stops quietly, as the reader didn't want the rest.
+pub fn run() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run_command(cli) {
+        Ok(code) => code,
+        Err(Error::Io { source, .. }) if source.kind() == io::ErrorKind::BrokenPipe => {
+            ExitCode::from(BROKEN_PIPE_EXIT_CODE)
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// a new empty dir in the temp dir.
+    fn tmp_dir() -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = env::temp_dir().join(format!(
+            "ugit-rs-cli-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    /// a work tree with an initialized repository dir in `dir`, return the repository dir.
+    fn init(work_tree: &Path) -> PathBuf {
+        let ugit_dir = work_tree.join(UGIT_REPOSITORY_NAME);
+        fs::create_dir_all(&ugit_dir).unwrap();
+        dirs::create_objects_dir(&ugit_dir).unwrap();
+
+        ugit_dir
+    }
+
+    fn location(ugit_dir: Option<&Path>, work_tree: Option<&Path>) -> Location {
+        Location {
+            ugit_dir: ugit_dir.map(Path::to_path_buf),
+            work_tree: work_tree.map(Path::to_path_buf),
+        }
+    }
+
+    #[test]
+    fn repositories_are_located_from_cwd_or_the_options() {
+        let root = tmp_dir();
+        let (work, other) = (root.join("work"), root.join("other"));
+        let (ugit_dir, other_ugit_dir) = (init(&work), init(&other));
+        let sub = work.join("sub");
+        fs::create_dir_all(&sub).unwrap();
+
+        // discovered from a subdir.
+        assert_eq!(
+            locate_repository_in(&location(None, None), sub.clone()),
+            Some((ugit_dir.clone(), work.clone()))
+        );
+        assert_eq!(
+            locate_repository_in(&location(None, None), root.clone()),
+            None
+        );
+
+        // the work tree is cwd, unless it's given too.
+        assert_eq!(
+            locate_repository_in(&location(Some(&other_ugit_dir), None), sub.clone()),
+            Some((other_ugit_dir.clone(), sub.clone()))
+        );
+        assert_eq!(
+            locate_repository_in(&location(Some(&other_ugit_dir), Some(&root)), sub.clone()),
+            Some((other_ugit_dir, root.clone()))
+        );
+        assert_eq!(
+            locate_repository_in(&location(None, Some(&root)), sub.clone()),
+            Some((ugit_dir, root.clone()))
+        );
+
+        // a repository dir given is not searched from.
+        assert_eq!(
+            locate_repository_in(&location(Some(&sub), None), sub.clone()),
+            None
+        );
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn options_win_over_environment_variables() {
+        let parse = |args: &[&str]| {
+            let cli = Cli::try_parse_from(["ugit-rs"].iter().chain(args)).unwrap();
+            (cli.ugit_dir, cli.work_tree)
+        };
+
+        // the only test which sets them, they're unset again before it ends.
+        env::set_var(UGIT_DIR_ENV, "/env/.ugit-rs");
+        env::set_var(UGIT_WORK_TREE_ENV, "/env");
+        let from_env = parse(&["write-tree"]);
+        let from_options = parse(&[
+            "--ugit-dir",
+            "/option/.ugit-rs",
+            "--work-tree",
+            "/option",
+            "write-tree",
+        ]);
+        let mixed = parse(&["--work-tree", "/option", "write-tree"]);
+        env::remove_var(UGIT_DIR_ENV);
+        env::remove_var(UGIT_WORK_TREE_ENV);
+
+        let paths = |ugit_dir: &str, work_tree: &str| {
+            (
+                Some(PathBuf::from(ugit_dir)),
+                Some(PathBuf::from(work_tree)),
+            )
+        };
+        assert_eq!(from_env, paths("/env/.ugit-rs", "/env"));
+        assert_eq!(from_options, paths("/option/.ugit-rs", "/option"));
+        assert_eq!(mixed, paths("/env/.ugit-rs", "/option"));
     }
 }
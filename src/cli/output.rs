@@ -0,0 +1,135 @@
+//! the output formats of the commands, see [`OutputFormat`].
+//!
+//! with `--format=json`, a command prints one JSON object and a newline. fields are
+//! only ever added to these schemas, never renamed or removed. paths are relative to the
+//! work tree root unless noted. a path which is not UTF-8 is `{"hex": ..}`, its bytes as
+//! hex, instead of a string. oids are hex,
+//! modes are octal strings like `"100644"`, and types are `"blob"`, `"tree"` or `"manifest"`.
+//!
+//! a tree entry is `{"mode": .., "type": .., "oid": .., "path": ..}`, whose `path` is
+//! relative to the tree.
+//!
+//! - `add`: `{"added": [{"path": .., "oid": ..}]}`;
+//! - `cat-file`: a blob is `{"oid": .., "type": "blob", "size": .., "encoding": .., "content": ..}`,
+//!   whose `content` is the content as text if `encoding` is `"utf-8"`, or as hex if
+//!   it's `"hex"`. a tree is `{"oid": .., "type": "tree", "entries": [tree entry]}`;
+//! - `check-ignore`: `{"paths": [{"path": .., "ignored": .., "pattern": ..}]}`, of every
+//!   path given, as given. `pattern` is null if no pattern matches, otherwise
+//!   `{"source": .., "line_number": .., "pattern": ..}`, which may be a negated one;
+//! - `config list`: `{"entries": [{"key": .., "value": .., "scope": .., "origin": ..}]}`,
+//!   whose `value` is null for a variable without `=`, and `origin` is the config file;
+//! - `ls-tree`: `{"entries": [listed entry]}`, a listed entry is a tree entry whose
+//!   `path` is relative to the tree listed, with a `size` which is the size of a blob
+//!   with `-l`, and null otherwise;
+//! - `hash-object`: `{"objects": [{"path": .., "type": .., "oid": ..}]}`, whose `path`
+//!   is the file or the dir hashed, `"."` for the work tree root, and absolute for a file
+//!   outside the work tree;
+//! - `read-tree`: `{"oid": .., "restored": [tree entry]}`, of the files restored;
+//! - `write-tree`: `{"oid": ..}`.
+
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+use ugit_rs::objects::tree_entry::TreeVirtualFileEntry;
+use ugit_rs::{Error, Result};
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// for humans, which may change.
+    #[default]
+    Text,
+    /// stable JSON for scripts.
+    Json,
+}
+
+/// `value` as JSON, in one line.
+pub fn to_json<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    serde_json::to_string(value).map_err(|e| Error::Io {
+        path: None,
+        source: e.into(),
+    })
+}
+
+/// print `value` as one line of JSON.
+pub fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    writeln!(io::stdout().lock(), "{}", to_json(value)?)?;
+
+    Ok(())
+}
+
+/// warn on stderr about each file left out of a tree since it can't be stored,
+/// see [`ugit_rs::Repository::build_tree`]. it's a warning in every output format.
+pub fn warn_skipped(skipped: &[PathBuf]) {
+    for path in skipped {
+        eprintln!(
+            "warning: skipping '{}', which is not a regular file, a symlink or a dir",
+            path.display()
+        );
+    }
+}
+
+/// a path in JSON, a string if it's UTF-8, otherwise `{"hex": ..}`, see [`json_path`].
+#[derive(Debug)]
+pub struct JsonPath(PathBuf);
+
+impl Serialize for JsonPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        match self.0.to_str() {
+            Some(path) => serializer.serialize_str(path),
+            None => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("hex", &hex::encode(self.0.as_os_str().as_bytes()))?;
+                map.end()
+            }
+        }
+    }
+}
+
+/// `path` in JSON, without losing the bytes of a path which is not UTF-8.
+pub fn json_path(path: &Path) -> JsonPath {
+    JsonPath(path.to_path_buf())
+}
+
+#[derive(Serialize)]
+pub struct JsonTreeEntry {
+    mode: String,
+    #[serde(rename = "type")]
+    obj_type: String,
+    oid: String,
+    path: JsonPath,
+}
+
+impl JsonTreeEntry {
+    /// `entry` of the tree at `dir`, relative to the tree the entry is listed in.
+    pub fn new(entry: &TreeVirtualFileEntry, dir: &Path) -> Self {
+        Self {
+            mode: entry.mode().to_string(),
+            obj_type: entry.obj_type().to_string(),
+            oid: entry.oid().to_string(),
+            path: json_path(&dir.join(entry.origin_file_name())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::*;
+
+    #[test]
+    fn paths_which_are_not_utf8_keep_their_bytes() {
+        let to_json = |path: &Path| serde_json::to_string(&json_path(path)).unwrap();
+
+        assert_eq!(to_json(Path::new("sub/a b.txt")), r#""sub/a b.txt""#);
+        assert_eq!(
+            to_json(Path::new(OsStr::from_bytes(b"sub/bad\xff"))),
+            r#"{"hex":"7375622f626164ff"}"#
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use ugit_rs::crypto::ObjectId;
+use ugit_rs::fs_tools::pathspec::Pathspec;
+use ugit_rs::objects::tree_entry::TreeVirtualFileEntry;
+use ugit_rs::{Repository, Result};
+
+use super::check_object;
+use super::output::{self, JsonTreeEntry, OutputFormat};
+
+#[derive(Serialize)]
+struct JsonReadTree {
+    oid: String,
+    restored: Vec<JsonTreeEntry>,
+}
+
+/// revert work dir from repo index.
+///
+/// with a `pathspec`, only the selected files are restored and the rest of the
+/// work dir is kept. prints `{mode} {type} {oid}\t{path}` for each file restored,
+/// like `ls-tree -r`.
+pub fn cmd_read_tree(
+    repo: &Repository,
+    oid: ObjectId,
+    pathspec: Vec<PathBuf>,
+    format: OutputFormat,
+) -> Result<()> {
+    check_object(repo, &oid)?;
+
+    let tree = repo.read_tree(oid)?;
+    let pathspec = Pathspec::new(&pathspec, repo.work_tree())?;
+    repo.restore_tree(&tree, &pathspec)?;
+
+    let mut restored = vec![];
+    for (path, mode, blob) in tree.files() {
+        if pathspec.matches(repo.work_tree().join(&path)) {
+            restored.push(TreeVirtualFileEntry::from_blob_obj(blob, mode, path)?);
+        }
+    }
+
+    match format {
+        OutputFormat::Text => {
+            let mut out = io::stdout().lock();
+            for entry in &restored {
+                writeln!(out, "{}", entry)?;
+            }
+        }
+        OutputFormat::Json => output::print_json(&JsonReadTree {
+            oid: oid.to_string(),
+            restored: restored
+                .iter()
+                .map(|entry| JsonTreeEntry::new(entry, Path::new("")))
+                .collect(),
+        })?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use ugit_rs::fs_tools::pathspec::Pathspec;
+use ugit_rs::{Repository, Result};
+
+use super::output::{self, OutputFormat};
+
+#[derive(Serialize)]
+struct JsonWriteTree {
+    oid: String,
+}
+
+/// This command will take the work tree and store it to the object database.
+/// If hash-object was for storing an individual file, then write-tree is for storing a whole directory.
+///
+/// only the files selected by `pathspec` are written, all of them if it's empty.
+pub fn cmd_write_tree(
+    repo: &Repository,
+    pathspec: Vec<PathBuf>,
+    format: OutputFormat,
+) -> Result<()> {
+    let pathspec = Pathspec::new(&pathspec, repo.work_tree())?;
+    let mut skipped = vec![];
+    let oid = repo.write_tree(&pathspec, &mut skipped)?;
+    output::warn_skipped(&skipped);
+
+    match format {
+        OutputFormat::Text => writeln!(io::stdout().lock(), "{}", oid)?,
+        OutputFormat::Json => output::print_json(&JsonWriteTree {
+            oid: oid.to_string(),
+        })?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,1055 @@
+//! the configuration of ugit-rs, read from INI-style files of three scopes,
+//! from lowest to highest precedence:
+//!
+//! 1. system, `/etc/ugit-rs/config`, or `$UGIT_CONFIG_SYSTEM`. skipped if
+//!    `$UGIT_CONFIG_NOSYSTEM` is set;
+//! 2. global, `$XDG_CONFIG_HOME/ugit-rs/config` or `$HOME/.config/ugit-rs/config`,
+//!    or `$UGIT_CONFIG_GLOBAL`;
+//! 3. local, `{ugit_dir}/config`, of the repository.
+//!
+//! the files have the syntax of git config files:
+//!
+//! ```text
+//! # a comment
+//! [core]
+//!     chunkedBlobs = true
+//! [section "subsection"]
+//!     name = "a value ; with a comment char"
+//! ```
+//!
+//! a variable is addressed by its key, `section.name` or `section.subsection.name`.
+//! section and variable names are case-insensitive, subsections are not.
+//! a variable may be set several times, the last value wins where one is expected,
+//! so a higher scope overrides a lower one.
+//!
+//! the variables ugit-rs reads are listed as the `*_KEY` constants.
+
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::crypto::HashAlgorithm;
+use crate::error::{Error, IoResultExt, Result};
+use crate::fs_tools::dirs::{self, CONFIG_FILE_NAME};
+use crate::fs_tools::lock::LockFile;
+
+/// environment variable overriding the path of the system config file.
+pub const CONFIG_SYSTEM_ENV: &str = "UGIT_CONFIG_SYSTEM";
+/// environment variable overriding the path of the global config file.
+pub const CONFIG_GLOBAL_ENV: &str = "UGIT_CONFIG_GLOBAL";
+/// environment variable which skips the system config file if it's set.
+pub const CONFIG_NOSYSTEM_ENV: &str = "UGIT_CONFIG_NOSYSTEM";
+
+const SYSTEM_CONFIG_PATH: &str = "/etc/ugit-rs/config";
+
+/// the hash algorithm of the repository, written at init. only read from the local scope,
+/// and `config` refuses to change it once the repository has objects, which would be
+/// unreadable with another hash algorithm.
+pub const OBJECT_FORMAT_KEY: &str = "extensions.objectFormat";
+/// bool, store large files as content-defined chunks, see [`crate::objects::chunked`].
+pub const CHUNKED_BLOBS_KEY: &str = "core.chunkedBlobs";
+/// path, the global excludes file, see [`crate::objects::ignored::IgnoreRules`].
+pub const EXCLUDES_FILE_KEY: &str = "core.excludesFile";
+/// the hash algorithm of repositories initialized without `--object-format`.
+pub const DEFAULT_OBJECT_FORMAT_KEY: &str = "init.defaultObjectFormat";
+/// list, the names of the files in the work tree root which are kept when `read-tree`
+/// replaces the whole work tree, see [`Config::get_list`].
+pub const READ_TREE_KEEP_KEY: &str = "readTree.keep";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConfigScope {
+    System,
+    Global,
+    Local,
+}
+
+impl ConfigScope {
+    /// all scopes, from lowest to highest precedence.
+    pub const ALL: [ConfigScope; 3] =
+        [ConfigScope::System, ConfigScope::Global, ConfigScope::Local];
+
+    /// the config file of the scope, [`None`] if there is none: the system scope is skipped,
+    /// `$HOME` is not set, or the local scope is asked for without a repository dir.
+    pub fn path(self, ugit_dir: Option<&Path>) -> Option<PathBuf> {
+        match self {
+            ConfigScope::System => match env::var_os(CONFIG_NOSYSTEM_ENV) {
+                Some(_) => None,
+                None => Some(
+                    env::var_os(CONFIG_SYSTEM_ENV)
+                        .map(PathBuf::from)
+                        .unwrap_or_else(|| PathBuf::from(SYSTEM_CONFIG_PATH)),
+                ),
+            },
+            ConfigScope::Global => env::var_os(CONFIG_GLOBAL_ENV)
+                .map(PathBuf::from)
+                .or_else(|| dirs::user_config_dir().map(|dir| dir.join(CONFIG_FILE_NAME))),
+            ConfigScope::Local => ugit_dir.map(|ugit_dir| ugit_dir.join(CONFIG_FILE_NAME)),
+        }
+    }
+}
+
+impl Display for ConfigScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigScope::System => write!(f, "system"),
+            ConfigScope::Global => write!(f, "global"),
+            ConfigScope::Local => write!(f, "local"),
+        }
+    }
+}
+
+/// a key split into its parts, as given.
+struct Key<'a> {
+    section: &'a str,
+    subsection: Option<&'a str>,
+    name: &'a str,
+}
+
+impl<'a> Key<'a> {
+    fn parse(key: &'a str) -> Result<Self> {
+        let invalid = || Error::invalid_argument(format!("invalid config key '{}'", key));
+
+        let (section, rest) = key.split_once('.').ok_or_else(invalid)?;
+        let (subsection, name) = match rest.rsplit_once('.') {
+            Some((subsection, name)) => (Some(subsection), name),
+            None => (None, rest),
+        };
+
+        if !is_valid_section(section)
+            || !is_valid_name(name)
+            || subsection.is_some_and(|subsection| subsection.contains(['\n', '\0']))
+        {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            section,
+            subsection,
+            name,
+        })
+    }
+
+    /// `section[.subsection]`, with the section lowercased.
+    fn canonical_section(&self) -> String {
+        match self.subsection {
+            Some(subsection) => format!("{}.{}", self.section.to_ascii_lowercase(), subsection),
+            None => self.section.to_ascii_lowercase(),
+        }
+    }
+
+    fn canonical(&self) -> String {
+        format!(
+            "{}.{}",
+            self.canonical_section(),
+            self.name.to_ascii_lowercase()
+        )
+    }
+
+    /// the section header line, as given.
+    fn header_line(&self) -> String {
+        match self.subsection {
+            Some(subsection) => format!(
+                "[{} \"{}\"]",
+                self.section,
+                subsection.replace('\\', "\\\\").replace('"', "\\\"")
+            ),
+            None => format!("[{}]", self.section),
+        }
+    }
+
+    fn entry_line(&self, value: &str) -> String {
+        format!("\t{} = {}", self.name, quote_value(value))
+    }
+}
+
+/// the canonical form of `key`, with the section and the variable name lowercased.
+///
+/// fails with [`Error::InvalidArgument`] if `key` is not `section[.subsection].name`.
+pub fn canonical_key(key: &str) -> Result<String> {
+    Ok(Key::parse(key)?.canonical())
+}
+
+fn is_valid_section(section: &str) -> bool {
+    !section.is_empty()
+        && section
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+fn is_valid_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// `value` as written in a config file, quoted if it would be changed by parsing otherwise.
+fn quote_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{8}' => escaped.push_str("\\b"),
+            c => escaped.push(c),
+        }
+    }
+
+    // unquoted whitespace is dropped around the value, and is a space within it.
+    let needs_quotes = escaped.starts_with(char::is_whitespace)
+        || escaped.ends_with(char::is_whitespace)
+        || escaped.contains(|c: char| c == '#' || c == ';' || (c.is_whitespace() && c != ' '));
+    match needs_quotes {
+        true => format!("\"{}\"", escaped),
+        false => escaped,
+    }
+}
+
+/// one value of a variable, and where it's set.
+#[derive(Debug, Clone)]
+pub struct ConfigEntry {
+    /// canonical, see [`canonical_key`].
+    key: String,
+    /// [`None`] for a variable without `=`, which is a true bool.
+    value: Option<String>,
+
+    scope: ConfigScope,
+    source: PathBuf,
+    /// 1-based, of the line the variable starts at.
+    line_number: usize,
+}
+
+impl ConfigEntry {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+
+    pub fn scope(&self) -> ConfigScope {
+        self.scope
+    }
+
+    /// the config file the entry is read from.
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// an [`Error::InvalidConfig`] at this entry.
+    pub fn invalid<S: Into<String>>(&self, reason: S) -> Error {
+        Error::InvalidConfig {
+            path: self.source.clone(),
+            line_number: self.line_number,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&str> {
+        self.value()
+            .ok_or_else(|| self.invalid(format!("missing value for '{}'", self.key)))
+    }
+
+    /// `true`, `yes`, `on` and `1` are true, `false`, `no`, `off`, `0` and the empty value
+    /// are false, case-insensitively. a variable without value is true.
+    pub fn as_bool(&self) -> Result<bool> {
+        let value = match self.value() {
+            Some(value) => value.to_ascii_lowercase(),
+            None => return Ok(true),
+        };
+
+        match value.as_str() {
+            "true" | "yes" | "on" | "1" => Ok(true),
+            "false" | "no" | "off" | "0" | "" => Ok(false),
+            _ => Err(self.invalid(format!("bad boolean value '{}' for '{}'", value, self.key))),
+        }
+    }
+
+    /// an integer, optionally with a `k`, `m` or `g` suffix which scales it by 1024,
+    /// 1024^2 or 1024^3.
+    pub fn as_int(&self) -> Result<i64> {
+        let value = self.as_str()?.trim();
+        let invalid = || self.invalid(format!("bad numeric value '{}' for '{}'", value, self.key));
+
+        let (digits, factor) = match value.chars().last().map(|c| c.to_ascii_lowercase()) {
+            Some('k') => (&value[..value.len() - 1], 1 << 10),
+            Some('m') => (&value[..value.len() - 1], 1 << 20),
+            Some('g') => (&value[..value.len() - 1], 1 << 30),
+            _ => (value, 1),
+        };
+
+        digits
+            .parse::<i64>()
+            .ok()
+            .and_then(|number| number.checked_mul(factor))
+            .ok_or_else(invalid)
+    }
+
+    /// a path, where a leading `~/` is the home dir. a relative path is relative to
+    /// the dir of the config file, not to cwd.
+    pub fn as_path(&self) -> Result<PathBuf> {
+        let value = self.as_str()?;
+
+        if let Some(under_home) = value.strip_prefix("~/") {
+            return match env::var_os("HOME") {
+                Some(home) => Ok(PathBuf::from(home).join(under_home)),
+                None => Err(self.invalid(format!(
+                    "can't expand '{}' for '{}', $HOME is not set",
+                    value, self.key
+                ))),
+            };
+        }
+
+        let base_dir = self.source.parent().unwrap_or(Path::new(""));
+        Ok(base_dir.join(value))
+    }
+
+    /// one of the values of [`HashAlgorithm`], like `sha256`.
+    pub fn as_object_format(&self) -> Result<HashAlgorithm> {
+        let value = self.as_str()?;
+
+        HashAlgorithm::try_from(value)
+            .map_err(|_| self.invalid(format!("unknown object format {}", value)))
+    }
+}
+
+/// `key=value`, or `key` for a variable without value.
+impl Display for ConfigEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.value {
+            Some(value) => write!(f, "{}={}", self.key, value),
+            None => write!(f, "{}", self.key),
+        }
+    }
+}
+
+/// the entries of the config files of some scopes, in order of precedence.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    entries: Vec<ConfigEntry>,
+}
+
+impl Config {
+    /// the config of all scopes. the local scope is only read with `ugit_dir`.
+    pub fn load(ugit_dir: Option<&Path>) -> Result<Self> {
+        Self::load_scopes(&ConfigScope::ALL, ugit_dir)
+    }
+
+    /// the config of `scopes`, see [`Self::load`].
+    pub fn load_scopes(scopes: &[ConfigScope], ugit_dir: Option<&Path>) -> Result<Self> {
+        let mut entries = vec![];
+
+        for scope in ConfigScope::ALL {
+            if !scopes.contains(&scope) {
+                continue;
+            }
+
+            if let Some(path) = scope.path(ugit_dir) {
+                entries.extend(ConfigFile::read(path, scope)?.into_entries());
+            }
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// all entries, from lowest to highest precedence.
+    pub fn entries(&self) -> &[ConfigEntry] {
+        &self.entries
+    }
+
+    /// all values of the variable `key`, from lowest to highest precedence.
+    pub fn get_all<'a>(&'a self, key: &str) -> impl Iterator<Item = &'a ConfigEntry> + 'a {
+        let key = canonical_key(key).ok();
+
+        self.entries
+            .iter()
+            .filter(move |entry| Some(entry.key.as_str()) == key.as_deref())
+    }
+
+    /// the value of the variable `key` which takes precedence, [`None`] if it's not set.
+    pub fn get(&self, key: &str) -> Option<&ConfigEntry> {
+        self.get_all(key).last()
+    }
+
+    pub fn get_str(&self, key: &str) -> Result<Option<&str>> {
+        self.get(key).map(ConfigEntry::as_str).transpose()
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>> {
+        self.get(key).map(ConfigEntry::as_bool).transpose()
+    }
+
+    pub fn get_int(&self, key: &str) -> Result<Option<i64>> {
+        self.get(key).map(ConfigEntry::as_int).transpose()
+    }
+
+    pub fn get_path(&self, key: &str) -> Result<Option<PathBuf>> {
+        self.get(key).map(ConfigEntry::as_path).transpose()
+    }
+
+    pub fn get_object_format(&self, key: &str) -> Result<Option<HashAlgorithm>> {
+        self.get(key).map(ConfigEntry::as_object_format).transpose()
+    }
+
+    /// the list of the multi-valued variable `key`: `defaults`, followed by all its values.
+    /// an empty value clears the list built so far, so it can drop the defaults.
+    pub fn get_list<'a>(&'a self, key: &str, defaults: &[&'a str]) -> Result<Vec<&'a str>> {
+        let mut list = defaults.to_vec();
+
+        for entry in self.get_all(key) {
+            match entry.as_str()? {
+                "" => list.clear(),
+                value => list.push(value),
+            }
+        }
+
+        Ok(list)
+    }
+}
+
+impl FromIterator<ConfigEntry> for Config {
+    fn from_iter<I: IntoIterator<Item = ConfigEntry>>(entries: I) -> Self {
+        Self {
+            entries: entries.into_iter().collect(),
+        }
+    }
+}
+
+/// an entry of a [`ConfigFile`], and where it's written.
+#[derive(Debug)]
+struct ParsedEntry {
+    entry: ConfigEntry,
+    /// canonical, see [`Key::canonical_section`].
+    section: String,
+    /// the lines the entry spans, it may be continued by a trailing `\`.
+    lines: Range<usize>,
+}
+
+/// one config file, which keeps its lines as written, so it can be edited in place.
+#[derive(Debug)]
+pub struct ConfigFile {
+    path: PathBuf,
+    scope: ConfigScope,
+    lines: Vec<String>,
+
+    /// the canonical name of each section header, and its line.
+    sections: Vec<(String, usize)>,
+    entries: Vec<ParsedEntry>,
+}
+
+impl ConfigFile {
+    /// the config file at `path`, which is empty if it does not exist.
+    ///
+    /// fails with [`Error::InvalidConfig`] if it can't be parsed.
+    pub fn read<P: AsRef<Path>>(path: P, scope: ConfigScope) -> Result<Self> {
+        let path = path.as_ref();
+
+        let content = match fs::read_to_string(path) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            content => content.with_path(path)?,
+        };
+
+        Self::parse(
+            path.to_path_buf(),
+            scope,
+            content.lines().map(str::to_owned).collect(),
+        )
+    }
+
+    /// edit the config file at `path` with `edit`, holding its lock, see [`LockFile`].
+    ///
+    /// the file is only written if `edit` changed it.
+    pub fn edit<P, F, T>(path: P, scope: ConfigScope, edit: F) -> Result<T>
+    where
+        P: AsRef<Path>,
+        F: FnOnce(&mut ConfigFile) -> Result<T>,
+    {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_path(parent)?;
+        }
+
+        let lock = LockFile::acquire(path)?;
+        let mut file = Self::read(path, scope)?;
+        let origin_lines = file.lines.clone();
+
+        let result = edit(&mut file)?;
+        if file.lines != origin_lines {
+            lock.write(file.to_string().as_bytes())?;
+        }
+
+        Ok(result)
+    }
+
+    fn parse(path: PathBuf, scope: ConfigScope, lines: Vec<String>) -> Result<Self> {
+        let invalid = |index: usize, reason: &str| Error::InvalidConfig {
+            path: path.clone(),
+            line_number: index + 1,
+            reason: reason.to_owned(),
+        };
+
+        let mut sections = vec![];
+        let mut entries = vec![];
+        let mut index = 0;
+
+        while index < lines.len() {
+            let start = index;
+            let line = lines[index].trim_start();
+            index += 1;
+
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix('[') {
+                let section = parse_header(header).map_err(|reason| invalid(start, reason))?;
+                sections.push((section, start));
+                continue;
+            }
+
+            let section = match sections.last() {
+                Some((section, _)) => section.clone(),
+                None => return Err(invalid(start, "variable outside of a section")),
+            };
+
+            let name_len = line
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+                .unwrap_or(line.len());
+            let (name, rest) = line.split_at(name_len);
+            if !is_valid_name(name) {
+                return Err(invalid(start, "invalid variable name"));
+            }
+
+            let rest = rest.trim_start();
+            let value = if rest.is_empty() || rest.starts_with(['#', ';']) {
+                None
+            } else if let Some(value) = rest.strip_prefix('=') {
+                Some(
+                    parse_value(value, &lines, &mut index)
+                        .map_err(|reason| invalid(start, reason))?,
+                )
+            } else {
+                return Err(invalid(start, "expected '=' after the variable name"));
+            };
+
+            entries.push(ParsedEntry {
+                entry: ConfigEntry {
+                    key: format!("{}.{}", section, name.to_ascii_lowercase()),
+                    value,
+                    scope,
+                    source: path.clone(),
+                    line_number: start + 1,
+                },
+                section,
+                lines: start..index,
+            });
+        }
+
+        Ok(Self {
+            path,
+            scope,
+            lines,
+            sections,
+            entries,
+        })
+    }
+
+    /// parse the lines again, after they're edited.
+    fn reparse(&mut self) -> Result<()> {
+        *self = Self::parse(
+            self.path.clone(),
+            self.scope,
+            std::mem::take(&mut self.lines),
+        )?;
+
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &ConfigEntry> {
+        self.entries.iter().map(|parsed| &parsed.entry)
+    }
+
+    fn into_entries(self) -> impl Iterator<Item = ConfigEntry> {
+        self.entries.into_iter().map(|parsed| parsed.entry)
+    }
+
+    /// set the variable `key` to `value`, replacing its value if it's set.
+    ///
+    /// fails with [`Error::InvalidArgument`] if it has several values, see [`Self::unset`].
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let parsed_key = Key::parse(key)?;
+        let canonical = parsed_key.canonical();
+
+        let mut existing = self
+            .entries
+            .iter()
+            .filter(|parsed| parsed.entry.key == canonical);
+
+        match (existing.next(), existing.next()) {
+            (None, _) => self.insert(&parsed_key, value),
+            (Some(parsed), None) => {
+                let lines = parsed.lines.clone();
+                self.lines.splice(lines, [parsed_key.entry_line(value)]);
+                self.reparse()
+            }
+            (Some(_), Some(_)) => Err(Error::invalid_argument(format!(
+                "can't overwrite the multiple values of '{}' with a single value",
+                key
+            ))),
+        }
+    }
+
+    /// add `value` to the variable `key`, keeping its other values.
+    pub fn add(&mut self, key: &str, value: &str) -> Result<()> {
+        self.insert(&Key::parse(key)?, value)
+    }
+
+    /// insert an entry after the last one in its section, or append the section
+    /// if there is none.
+    fn insert(&mut self, key: &Key, value: &str) -> Result<()> {
+        let section = key.canonical_section();
+
+        let after_entry = self
+            .entries
+            .iter()
+            .rfind(|parsed| parsed.section == section)
+            .map(|parsed| parsed.lines.end);
+        let after_header = self
+            .sections
+            .iter()
+            .rfind(|(name, _)| *name == section)
+            .map(|(_, header_line)| header_line + 1);
+
+        match after_entry.max(after_header) {
+            Some(index) => self.lines.insert(index, key.entry_line(value)),
+            None => {
+                self.lines.push(key.header_line());
+                self.lines.push(key.entry_line(value));
+            }
+        }
+
+        self.reparse()
+    }
+
+    /// remove all values of the variable `key`, return how many there were.
+    pub fn unset(&mut self, key: &str) -> Result<usize> {
+        let canonical = canonical_key(key)?;
+
+        let removed: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|parsed| parsed.entry.key == canonical)
+            .map(|parsed| parsed.lines.clone())
+            .collect();
+
+        for lines in removed.iter().rev() {
+            self.lines.drain(lines.clone());
+        }
+        self.reparse()?;
+
+        Ok(removed.len())
+    }
+}
+
+/// the lines, as they're written to the file.
+impl Display for ConfigFile {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// the canonical section of the header `header`, which follows the `[`:
+/// `section]` or `section "subsection"]`, optionally followed by a comment.
+fn parse_header(header: &str) -> std::result::Result<String, &'static str> {
+    let name_len = header
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '.'))
+        .unwrap_or(header.len());
+    let (name, rest) = header.split_at(name_len);
+    if name.is_empty() {
+        return Err("invalid section name");
+    }
+
+    let mut section = name.to_ascii_lowercase();
+    let mut rest = rest.trim_start();
+
+    if let Some(quoted) = rest.strip_prefix('"') {
+        let mut subsection = String::new();
+        let mut chars = quoted.char_indices();
+
+        rest = loop {
+            match chars.next() {
+                Some((_, '\\')) => match chars.next() {
+                    Some((_, c)) => subsection.push(c),
+                    None => return Err("unterminated subsection name"),
+                },
+                Some((index, '"')) => break &quoted[index + 1..],
+                Some((_, c)) => subsection.push(c),
+                None => return Err("unterminated subsection name"),
+            }
+        };
+
+        section.push('.');
+        section.push_str(&subsection);
+    }
+
+    match rest.strip_prefix(']').map(str::trim_start) {
+        Some(trailing) if trailing.is_empty() || trailing.starts_with(['#', ';']) => Ok(section),
+        Some(_) => Err("unexpected content after the section header"),
+        None => Err("expected ']' at the end of the section header"),
+    }
+}
+
+/// the value which follows the `=`, from `first`. a trailing `\` continues the value
+/// on the next line of `lines`, which is at `next_index`.
+///
+/// whitespace around the value is dropped and each whitespace char within it is a space,
+/// unless it's quoted. `#` and `;` start a comment, unless they're quoted.
+fn parse_value(
+    first: &str,
+    lines: &[String],
+    next_index: &mut usize,
+) -> std::result::Result<String, &'static str> {
+    let mut value = String::new();
+    let mut pending_spaces = 0;
+    let mut quoted = false;
+    let mut line = first;
+
+    'lines: loop {
+        let mut chars = line.chars();
+
+        while let Some(c) = chars.next() {
+            if !quoted && c.is_whitespace() {
+                if !value.is_empty() {
+                    pending_spaces += 1;
+                }
+                continue;
+            }
+            if !quoted && (c == '#' || c == ';') {
+                break 'lines;
+            }
+
+            value.extend(std::iter::repeat_n(' ', pending_spaces));
+            pending_spaces = 0;
+
+            match c {
+                '\\' => match chars.next() {
+                    None if *next_index < lines.len() => {
+                        line = &lines[*next_index];
+                        *next_index += 1;
+                        continue 'lines;
+                    }
+                    None => break 'lines,
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('b') => value.push('\u{8}'),
+                    Some(escaped @ ('\\' | '"')) => value.push(escaped),
+                    Some(_) => return Err("invalid escape sequence"),
+                },
+                '"' => quoted = !quoted,
+                c => value.push(c),
+            }
+        }
+
+        break;
+    }
+
+    match quoted {
+        true => Err("unterminated quoted value"),
+        false => Ok(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str) -> ConfigFile {
+        ConfigFile::parse(
+            PathBuf::from("config"),
+            ConfigScope::Local,
+            content.lines().map(str::to_owned).collect(),
+        )
+        .unwrap()
+    }
+
+    fn value(first: &str) -> std::result::Result<String, &'static str> {
+        parse_value(first, &[], &mut 0)
+    }
+
+    fn entry(value: Option<&str>) -> ConfigEntry {
+        ConfigEntry {
+            key: "section.name".to_owned(),
+            value: value.map(str::to_owned),
+            scope: ConfigScope::Local,
+            source: PathBuf::from("config"),
+            line_number: 1,
+        }
+    }
+
+    #[test]
+    fn values_are_trimmed_unless_quoted() {
+        assert_eq!(value(" plain value ").unwrap(), "plain value");
+        assert_eq!(value("a\tb").unwrap(), "a b");
+        assert_eq!(value("a  b").unwrap(), "a  b");
+        assert_eq!(value(" \"  quoted  \" ").unwrap(), "  quoted  ");
+        assert_eq!(value("a \"b  c\" d").unwrap(), "a b  c d");
+        assert_eq!(value("").unwrap(), "");
+        assert_eq!(value("\"\"").unwrap(), "");
+        assert_eq!(value("\"unterminated"), Err("unterminated quoted value"));
+    }
+
+    #[test]
+    fn inline_comments() {
+        assert_eq!(value("value ; a comment").unwrap(), "value");
+        assert_eq!(value("value # a comment").unwrap(), "value");
+        assert_eq!(value("value#comment").unwrap(), "value");
+        assert_eq!(value("\"a ; b # c\" ; d").unwrap(), "a ; b # c");
+        assert_eq!(value("; only a comment").unwrap(), "");
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(value("a\\nb").unwrap(), "a\nb");
+        assert_eq!(value("a\\tb").unwrap(), "a\tb");
+        assert_eq!(value("a\\bb").unwrap(), "a\u{8}b");
+        assert_eq!(value("a\\\\b").unwrap(), "a\\b");
+        assert_eq!(value("a\\\"b").unwrap(), "a\"b");
+        assert_eq!(value("\"a\\\"b\"").unwrap(), "a\"b");
+        assert_eq!(value("a\\xb"), Err("invalid escape sequence"));
+    }
+
+    #[test]
+    fn line_continuations() {
+        let lines = ["two \\".to_owned(), "three".to_owned(), "four".to_owned()];
+        let mut next_index = 0;
+
+        assert_eq!(
+            parse_value("one \\", &lines, &mut next_index).unwrap(),
+            "one two three"
+        );
+        assert_eq!(next_index, 2);
+
+        // within quotes, and at the last line, which ends the value.
+        let mut next_index = 0;
+        assert_eq!(
+            parse_value("\"a \\", &["b\"".to_owned()], &mut next_index).unwrap(),
+            "a b"
+        );
+        assert_eq!(next_index, 1);
+        assert_eq!(value("last \\").unwrap(), "last ");
+
+        let file = parse("[a]\n\tk = one \\\n\t  two\n\tl = three");
+        let values: Vec<_> = file.entries().map(ConfigEntry::to_string).collect();
+        assert_eq!(values, ["a.k=one    two", "a.l=three"]);
+        assert_eq!(file.entries[0].lines, 1..3);
+        assert_eq!(file.entries[1].entry.line_number(), 4);
+    }
+
+    #[test]
+    fn headers() {
+        assert_eq!(parse_header("core]").unwrap(), "core");
+        assert_eq!(parse_header("Core] # a comment").unwrap(), "core");
+        assert_eq!(parse_header("a.b-c]").unwrap(), "a.b-c");
+        assert_eq!(
+            parse_header("Section \"Sub Section\"]").unwrap(),
+            "section.Sub Section"
+        );
+        assert_eq!(
+            parse_header("s \"a\\\"b\\\\c\"] ; a comment").unwrap(),
+            "s.a\"b\\c"
+        );
+        assert_eq!(parse_header("s \"\"]").unwrap(), "s.");
+        assert_eq!(parse_header("s \"a ] b\"]").unwrap(), "s.a ] b");
+
+        assert!(parse_header("]").is_err());
+        assert!(parse_header("s \"unterminated]").is_err());
+        assert!(parse_header("s \"x\"").is_err());
+        assert!(parse_header("s").is_err());
+        assert!(parse_header("s] trailing").is_err());
+        assert!(parse_header("s \"x\" y]").is_err());
+    }
+
+    const CONTENT: &str = "\
+# a leading comment
+[core]
+\t; a note
+\tchunkedBlobs = false # inline
+\texcludesFile = ~/ignore
+[section \"Sub\"]
+\tname = one
+\tname = two \\
+\t  continued
+
+; a trailing comment";
+
+    fn lines(file: &ConfigFile) -> Vec<&str> {
+        file.lines.iter().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn set_replaces_only_its_line() {
+        let mut file = parse(CONTENT);
+        file.set("CORE.ChunkedBlobs", "true").unwrap();
+
+        let mut expected: Vec<_> = CONTENT.lines().collect();
+        expected[3] = "\tChunkedBlobs = true";
+        assert_eq!(lines(&file), expected);
+        assert_eq!(
+            Config::from_iter(file.entries().cloned())
+                .get_bool(CHUNKED_BLOBS_KEY)
+                .unwrap(),
+            Some(true)
+        );
+
+        assert!(matches!(
+            file.set("section.Sub.name", "three"),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert_eq!(lines(&file), expected);
+    }
+
+    #[test]
+    fn set_and_add_insert_into_their_section() {
+        let mut file = parse(CONTENT);
+        file.add("section.Sub.name", "three").unwrap();
+        file.set("core.editor", "vi").unwrap();
+        file.set("new.Sub.key", "a ; b").unwrap();
+
+        let mut expected: Vec<_> = CONTENT.lines().collect();
+        expected.insert(9, "\tname = three");
+        expected.insert(5, "\teditor = vi");
+        expected.extend(["[new \"Sub\"]", "\tkey = \"a ; b\""]);
+        assert_eq!(lines(&file), expected);
+
+        let config = Config::from_iter(file.entries().cloned());
+        let names: Vec<_> = config
+            .get_all("section.Sub.name")
+            .map(|entry| entry.value().unwrap())
+            .collect();
+        assert_eq!(names, ["one", "two    continued", "three"]);
+        assert_eq!(config.get_str("new.Sub.key").unwrap(), Some("a ; b"));
+        // subsections are case-sensitive.
+        assert_eq!(config.get("section.sub.name").map(ConfigEntry::key), None);
+    }
+
+    #[test]
+    fn unset_removes_all_values_and_their_continuations() {
+        let mut file = parse(CONTENT);
+        assert_eq!(file.unset("section.Sub.name").unwrap(), 2);
+        assert_eq!(file.unset("section.Sub.name").unwrap(), 0);
+        assert_eq!(file.unset("core.excludesfile").unwrap(), 1);
+
+        let mut expected: Vec<_> = CONTENT.lines().collect();
+        expected.drain(6..9);
+        expected.remove(4);
+        assert_eq!(lines(&file), expected);
+        assert_eq!(file.entries().count(), 1);
+    }
+
+    #[test]
+    fn quoted_values_parse_back() {
+        let values = [
+            "plain",
+            "",
+            "  ",
+            " leading",
+            "trailing ",
+            "inner  spaces",
+            "a ; b",
+            "a # b",
+            "quote \" and \\ backslash",
+            "two\nlines",
+            "tab\there",
+            "\ttab",
+            "back\u{8}space",
+            "carriage\rreturn",
+            "\u{a0}no-break space",
+            "\\",
+        ];
+
+        for value in values {
+            let key = Key::parse("a.b.k").unwrap();
+            let file = parse(&format!("{}\n{}", key.header_line(), key.entry_line(value)));
+            let parsed: Vec<_> = file.entries().map(ConfigEntry::value).collect();
+
+            assert_eq!(parsed, [Some(value)], "{:?} is not parsed back", value);
+        }
+    }
+
+    #[test]
+    fn ints() {
+        assert_eq!(entry(Some("42")).as_int().unwrap(), 42);
+        assert_eq!(entry(Some("-7")).as_int().unwrap(), -7);
+        assert_eq!(entry(Some("0")).as_int().unwrap(), 0);
+        assert_eq!(entry(Some("1k")).as_int().unwrap(), 1 << 10);
+        assert_eq!(entry(Some("2M")).as_int().unwrap(), 2 << 20);
+        assert_eq!(entry(Some("3g")).as_int().unwrap(), 3 << 30);
+        assert_eq!(entry(Some("-1K")).as_int().unwrap(), -1024);
+        assert_eq!(entry(Some(" 5 ")).as_int().unwrap(), 5);
+        assert_eq!(
+            entry(Some(&i64::MAX.to_string())).as_int().unwrap(),
+            i64::MAX
+        );
+
+        for invalid in [
+            "",
+            "k",
+            "1.5",
+            "1.5k",
+            "0x10",
+            "1kb",
+            "ten",
+            "9223372036854775807k",
+        ] {
+            assert!(
+                matches!(
+                    entry(Some(invalid)).as_int(),
+                    Err(Error::InvalidConfig { .. })
+                ),
+                "{:?} is an int",
+                invalid
+            );
+        }
+        assert!(matches!(
+            entry(None).as_int(),
+            Err(Error::InvalidConfig { .. })
+        ));
+    }
+
+    #[test]
+    fn bools() {
+        assert!(entry(None).as_bool().unwrap());
+        for value in ["true", "TRUE", "Yes", "on", "1"] {
+            assert!(entry(Some(value)).as_bool().unwrap(), "{:?}", value);
+        }
+        for value in ["false", "No", "OFF", "0", ""] {
+            assert!(!entry(Some(value)).as_bool().unwrap(), "{:?}", value);
+        }
+        for value in ["2", "-1", "truthy", "y", "t"] {
+            assert!(
+                matches!(
+                    entry(Some(value)).as_bool(),
+                    Err(Error::InvalidConfig { .. })
+                ),
+                "{:?} is a bool",
+                value
+            );
+        }
+    }
+}
@@ -1,14 +1,116 @@
+//! hash algorithms used to compute object ids.
+//!
+//! a repository picks its algorithm once, at `init --object-format=<algorithm>`,
+//! and every oid in that repository is computed with it.
+
+use std::fmt::{Display, Formatter};
+use std::io::{self, Read};
+
+use clap::ValueEnum;
 use sha1::{Digest, Sha1};
+use sha2::Sha256;
+
+pub use object_id::ObjectId;
 
-pub fn sha1_to_array(buf: &Vec<u8>) -> [u8; 20] {
-    // TODO: use global hasher
-    let mut hasher = Sha1::new();
+pub mod object_id;
 
-    hasher.update(buf);
-    hasher.finalize().into()
+/// size of the buffer used when streaming contents to hash them,
+/// so memory stays flat regardless of their size. file streams use it too.
+pub const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, ValueEnum)]
+pub enum HashAlgorithm {
+    #[default]
+    Sha1,
+    Sha256,
 }
 
-pub fn sha1_to_string(buf: &Vec<u8>) -> String {
-    let hash_val = sha1_to_array(buf);
-    hex::encode(&hash_val)
+impl HashAlgorithm {
+    /// length of the raw digest, in bytes.
+    pub fn raw_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 => 32,
+        }
+    }
+
+    /// length of the digest encoded as hex.
+    pub fn hex_len(&self) -> usize {
+        self.raw_len() * 2
+    }
+
+    pub fn hasher(&self) -> ObjectHasher {
+        match self {
+            HashAlgorithm::Sha1 => ObjectHasher::Sha1(Sha1::new()),
+            HashAlgorithm::Sha256 => ObjectHasher::Sha256(Sha256::new()),
+        }
+    }
+
+    pub fn digest(&self, buf: &[u8]) -> ObjectId {
+        let mut hasher = self.hasher();
+
+        hasher.update(buf);
+        hasher.finalize()
+    }
+
+    /// hash everything `reader` yields, in chunks of [`STREAM_CHUNK_SIZE`].
+    pub fn digest_reader<R: Read>(&self, mut reader: R) -> io::Result<ObjectId> {
+        let mut hasher = self.hasher();
+        let mut buf = vec![0; STREAM_CHUNK_SIZE];
+
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => hasher.update(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(hasher.finalize())
+    }
+}
+
+impl Display for HashAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashAlgorithm::Sha1 => write!(f, "sha1"),
+            HashAlgorithm::Sha256 => write!(f, "sha256"),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for HashAlgorithm {
+    type Error = ();
+
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
+        match value {
+            "sha1" => Ok(Self::Sha1),
+            "sha256" => Ok(Self::Sha256),
+            _ => Err(()),
+        }
+    }
+}
+
+/// incremental hasher of a [`HashAlgorithm`].
+#[derive(Clone)]
+pub enum ObjectHasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl ObjectHasher {
+    pub fn update(&mut self, buf: &[u8]) {
+        match self {
+            ObjectHasher::Sha1(hasher) => hasher.update(buf),
+            ObjectHasher::Sha256(hasher) => hasher.update(buf),
+        }
+    }
+
+    pub fn finalize(self) -> ObjectId {
+        match self {
+            ObjectHasher::Sha1(hasher) => ObjectId::Sha1(hasher.finalize().into()),
+            ObjectHasher::Sha256(hasher) => ObjectId::Sha256(hasher.finalize().into()),
+        }
+    }
 }
@@ -0,0 +1,197 @@
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::str::FromStr;
+
+use super::HashAlgorithm;
+
+/// the digest of an object, tagged with the algorithm which produced it.
+///
+/// the variant carries the length of the id, so ids of different
+/// object formats can never be confused with each other.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ObjectId {
+    Sha1([u8; 20]),
+    Sha256([u8; 32]),
+}
+
+impl ObjectId {
+    pub fn algorithm(&self) -> HashAlgorithm {
+        match self {
+            ObjectId::Sha1(_) => HashAlgorithm::Sha1,
+            ObjectId::Sha256(_) => HashAlgorithm::Sha256,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            ObjectId::Sha1(bytes) => bytes,
+            ObjectId::Sha256(bytes) => bytes,
+        }
+    }
+
+    /// raw digest bytes -> Self
+    ///
+    /// Returns [`None`] if `bytes` is not exactly as long as a digest of `algorithm`.
+    pub fn from_bytes(algorithm: HashAlgorithm, bytes: &[u8]) -> Option<Self> {
+        match algorithm {
+            HashAlgorithm::Sha1 => bytes.try_into().ok().map(ObjectId::Sha1),
+            HashAlgorithm::Sha256 => bytes.try_into().ok().map(ObjectId::Sha256),
+        }
+    }
+
+    /// parse a full, lowercase hex oid which must have been computed by `algorithm`.
+    pub fn from_hex(hex: &str, algorithm: HashAlgorithm) -> Result<Self, ParseObjectIdError> {
+        if hex.len() != algorithm.hex_len() {
+            return Err(ParseObjectIdError::InvalidLength(hex.len()));
+        }
+
+        if let Some(c) = hex.chars().find(|c| !matches!(c, '0'..='9' | 'a'..='f')) {
+            return Err(ParseObjectIdError::InvalidChar(c));
+        }
+
+        let bytes = hex::decode(hex).map_err(|_| ParseObjectIdError::InvalidLength(hex.len()))?;
+
+        Self::from_bytes(algorithm, &bytes).ok_or(ParseObjectIdError::InvalidLength(hex.len()))
+    }
+}
+
+impl FromStr for ObjectId {
+    type Err = ParseObjectIdError;
+
+    /// the algorithm is inferred from the length of `s`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [HashAlgorithm::Sha1, HashAlgorithm::Sha256]
+            .into_iter()
+            .find(|algorithm| algorithm.hex_len() == s.len())
+            .ok_or(ParseObjectIdError::InvalidLength(s.len()))
+            .and_then(|algorithm| Self::from_hex(s, algorithm))
+    }
+}
+
+impl Display for ObjectId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", hex::encode(self.as_bytes()))
+    }
+}
+
+impl Debug for ObjectId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ObjectId({}:{})", self.algorithm(), self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseObjectIdError {
+    /// the string is not as long as the hex digest of any supported algorithm.
+    InvalidLength(usize),
+    /// only lowercase hex digits are allowed.
+    InvalidChar(char),
+}
+
+impl Display for ParseObjectIdError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseObjectIdError::InvalidLength(len) => {
+                write!(f, "invalid object id length {}", len)
+            }
+            ParseObjectIdError::InvalidChar(c) => {
+                write!(f, "invalid character {:?} in object id", c)
+            }
+        }
+    }
+}
+
+impl Error for ParseObjectIdError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_BLOB_SHA1: &str = "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391";
+    const EMPTY_BLOB_SHA256: &str =
+        "473a0f4c3be8a93681a267e3b1e9a7dcda1185436fe141f7749120a303721813";
+
+    #[test]
+    fn from_hex() {
+        let oid = ObjectId::from_hex(EMPTY_BLOB_SHA1, HashAlgorithm::Sha1).unwrap();
+        assert_eq!(oid.algorithm(), HashAlgorithm::Sha1);
+        assert_eq!(oid.to_string(), EMPTY_BLOB_SHA1);
+
+        let oid = ObjectId::from_hex(EMPTY_BLOB_SHA256, HashAlgorithm::Sha256).unwrap();
+        assert_eq!(oid.algorithm(), HashAlgorithm::Sha256);
+        assert_eq!(oid.to_string(), EMPTY_BLOB_SHA256);
+    }
+
+    #[test]
+    fn from_hex_checks_the_width_of_the_algorithm() {
+        assert_eq!(
+            ObjectId::from_hex(EMPTY_BLOB_SHA1, HashAlgorithm::Sha256),
+            Err(ParseObjectIdError::InvalidLength(40))
+        );
+        assert_eq!(
+            ObjectId::from_hex(EMPTY_BLOB_SHA256, HashAlgorithm::Sha1),
+            Err(ParseObjectIdError::InvalidLength(64))
+        );
+        assert_eq!(
+            ObjectId::from_hex(&EMPTY_BLOB_SHA1[..39], HashAlgorithm::Sha1),
+            Err(ParseObjectIdError::InvalidLength(39))
+        );
+        assert_eq!(
+            ObjectId::from_hex("", HashAlgorithm::Sha1),
+            Err(ParseObjectIdError::InvalidLength(0))
+        );
+    }
+
+    #[test]
+    fn from_hex_rejects_uppercase_and_non_hex() {
+        let uppercase = EMPTY_BLOB_SHA1.to_uppercase();
+        assert_eq!(
+            ObjectId::from_hex(&uppercase, HashAlgorithm::Sha1),
+            Err(ParseObjectIdError::InvalidChar('E'))
+        );
+
+        let non_hex = EMPTY_BLOB_SHA1.replacen('e', "g", 1);
+        assert_eq!(
+            ObjectId::from_hex(&non_hex, HashAlgorithm::Sha1),
+            Err(ParseObjectIdError::InvalidChar('g'))
+        );
+
+        // as long in bytes as a sha1 oid, but not in chars.
+        let non_ascii = format!("é{}", &EMPTY_BLOB_SHA1[2..]);
+        assert_eq!(
+            ObjectId::from_hex(&non_ascii, HashAlgorithm::Sha1),
+            Err(ParseObjectIdError::InvalidChar('é'))
+        );
+    }
+
+    #[test]
+    fn from_str_infers_the_algorithm() {
+        let oid: ObjectId = EMPTY_BLOB_SHA1.parse().unwrap();
+        assert_eq!(oid.algorithm(), HashAlgorithm::Sha1);
+
+        let oid: ObjectId = EMPTY_BLOB_SHA256.parse().unwrap();
+        assert_eq!(oid.algorithm(), HashAlgorithm::Sha256);
+
+        assert_eq!(
+            EMPTY_BLOB_SHA256[..50].parse::<ObjectId>(),
+            Err(ParseObjectIdError::InvalidLength(50))
+        );
+        assert_eq!(
+            EMPTY_BLOB_SHA256.to_uppercase().parse::<ObjectId>(),
+            Err(ParseObjectIdError::InvalidChar('A'))
+        );
+    }
+
+    #[test]
+    fn from_hex_matches_the_digest() {
+        for (algorithm, hex) in [
+            (HashAlgorithm::Sha1, EMPTY_BLOB_SHA1),
+            (HashAlgorithm::Sha256, EMPTY_BLOB_SHA256),
+        ] {
+            assert_eq!(
+                ObjectId::from_hex(hex, algorithm).unwrap(),
+                algorithm.digest(b"blob 0\0")
+            );
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! the error type of ugit-rs, every fallible operation returns [`Result`].
+
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::crypto::ObjectId;
+use crate::objects::type_literal::ObjectTypeLiteral;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// there is no initialized repository dir at `path`, or with `searched_parents`,
+    /// in `path` or any of its parent dirs.
+    NotARepository {
+        path: PathBuf,
+        searched_parents: bool,
+    },
+
+    /// there is no object with the oid in the object database.
+    MissingObject(ObjectId),
+
+    /// an object can't be parsed. `oid` is [`None`] while the content is parsed
+    /// apart from the object it's read from, see [`Error::in_object`].
+    CorruptObject {
+        oid: Option<ObjectId>,
+        reason: String,
+    },
+
+    /// an object is not of the type it's used as.
+    TypeMismatch {
+        oid: ObjectId,
+        expected: ObjectTypeLiteral,
+        actual: ObjectTypeLiteral,
+    },
+
+    /// reading or writing `path` failed. `path` is [`None`] for errors of a stream,
+    /// e.g. stdout, whose source is not known where the error surfaces.
+    Io {
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+
+    /// the arguments given are wrong, e.g. a pathspec which can't be parsed.
+    InvalidArgument(String),
+
+    /// a config file can't be parsed, or the value of a variable is not of its type.
+    /// `line_number` is 1-based.
+    InvalidConfig {
+        path: PathBuf,
+        line_number: usize,
+        reason: String,
+    },
+
+    /// another process holds the lock at `lock_path`, see [`crate::fs_tools::lock`].
+    /// `pid` is [`None`] if the holder can't be read from the lock file.
+    /// the lock is `stale` if it was left behind by a process which is not running anymore.
+    Locked {
+        lock_path: PathBuf,
+        pid: Option<u32>,
+        stale: bool,
+    },
+}
+
+impl Error {
+    pub fn corrupt_object<S: Into<String>>(reason: S) -> Self {
+        Self::CorruptObject {
+            oid: None,
+            reason: reason.into(),
+        }
+    }
+
+    pub fn invalid_argument<S: Into<String>>(message: S) -> Self {
+        Self::InvalidArgument(message.into())
+    }
+
+    /// attribute a [`Error::CorruptObject`] without oid to the object with `oid`.
+    pub fn in_object(self, oid: &ObjectId) -> Self {
+        match self {
+            Self::CorruptObject { oid: None, reason } => Self::CorruptObject {
+                oid: Some(*oid),
+                reason,
+            },
+            other => other,
+        }
+    }
+
+    /// wrap into an [`io::Error`], to pass it through a [`io::Read`] impl.
+    pub fn into_io(self) -> io::Error {
+        match self {
+            Error::Io { path: None, source } => source,
+            other => io::Error::other(other),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotARepository {
+                path,
+                searched_parents: false,
+            } => write!(f, "not a ugit repository: '{}'", path.display()),
+            Error::NotARepository {
+                path,
+                searched_parents: true,
+            } => write!(
+                f,
+                "not a ugit repository (or any of the parent directories): '{}', please init first",
+                path.display()
+            ),
+            Error::MissingObject(oid) => write!(f, "the object {} does not exist", oid),
+            Error::CorruptObject {
+                oid: Some(oid),
+                reason,
+            } => write!(f, "corrupt object {}: {}", oid, reason),
+            Error::CorruptObject { oid: None, reason } => write!(f, "corrupt object: {}", reason),
+            Error::TypeMismatch {
+                oid,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "the object {} is a {} object, expected a {} object",
+                oid, actual, expected
+            ),
+            Error::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "{}: {}", path.display(), source),
+            Error::Io { path: None, source } => write!(f, "{}", source),
+            Error::InvalidArgument(message) => write!(f, "{}", message),
+            Error::InvalidConfig {
+                path,
+                line_number,
+                reason,
+            } => write!(
+                f,
+                "bad config line {} in '{}': {}",
+                line_number,
+                path.display(),
+                reason
+            ),
+            Error::Locked {
+                lock_path,
+                pid,
+                stale,
+            } => {
+                write!(f, "unable to lock '{}'", lock_path.display())?;
+                if let Some(pid) = pid {
+                    write!(f, ", held by the process {}", pid)?;
+                }
+                if *stale {
+                    write!(
+                        f,
+                        ". it was left behind by a ugit-rs process which is not running anymore, \
+                         remove the file and try again"
+                    )
+                } else {
+                    write!(
+                        f,
+                        ". another ugit-rs process seems to be running, \
+                         if it's not, remove the file and try again"
+                    )
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// an io error of a stream.
+///
+/// an [`Error`] passed through a [`io::Read`] (see [`Error::into_io`]) is unwrapped again.
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        if e.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            let inner = e.into_inner().expect("the inner error was checked");
+            return *inner.downcast().expect("the inner error was checked");
+        }
+
+        Self::Io {
+            path: None,
+            source: e,
+        }
+    }
+}
+
+/// adds the path to the error of an io operation on it.
+///
+/// an [`Error`] passed through the operation, see [`Error::into_io`], is kept as it is.
+pub trait IoResultExt<T> {
+    fn with_path<P: AsRef<Path>>(self, path: P) -> Result<T>;
+}
+
+impl<T> IoResultExt<T> for io::Result<T> {
+    fn with_path<P: AsRef<Path>>(self, path: P) -> Result<T> {
+        self.map_err(|e| match Error::from(e) {
+            Error::Io { path: None, source } => Error::Io {
+                path: Some(path.as_ref().to_path_buf()),
+                source,
+            },
+            other => other,
+        })
+    }
+}
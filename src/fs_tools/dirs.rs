@@ -1,35 +1,125 @@
-use once_cell::sync::Lazy;
-use std::{fs, io, ops::Deref, path::Path};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::error::{IoResultExt, Result};
 
 pub const UGIT_REPOSITORY_NAME: &str = ".ugit-rs";
 pub const OBJECTS_DIR_NAME: &str = "objects";
-pub static OBJECTS_DIR_PATH: Lazy<String> =
-    Lazy::new(|| format!("{}/{}", UGIT_REPOSITORY_NAME, OBJECTS_DIR_NAME));
+pub const INFO_EXCLUDE_FILE_NAME: &str = "info/exclude";
+pub const CONFIG_FILE_NAME: &str = "config";
 
-/// create dir `{cwd}/{UGIT_REPOSITORY_NAME}`
-pub fn create_repo_dir() -> io::Result<()> {
-    fs::create_dir(UGIT_REPOSITORY_NAME)
+/// create dir `{ugit_dir}`, the repository dir.
+pub fn create_repo_dir(ugit_dir: &Path) -> Result<()> {
+    fs::create_dir(ugit_dir).with_path(ugit_dir)
 }
 
-/// create dir `{cwd}/{UGIT_REPOSITORY_NAME}/{OBJECTS_DIR_NAME}`
-pub fn create_objects_dir() -> io::Result<()> {
-    fs::create_dir(&OBJECTS_DIR_PATH.deref())
+/// create dir `{ugit_dir}/{OBJECTS_DIR_NAME}`
+pub fn create_objects_dir(ugit_dir: &Path) -> Result<()> {
+    let objects_dir = ugit_dir.join(OBJECTS_DIR_NAME);
+    fs::create_dir(&objects_dir).with_path(objects_dir)
 }
 
-pub fn check_init() {
-    if !(is_repo_dir_exist() && is_objects_dir_exist()) {
-        print_and_exit!("please init first")
+/// whether `{ugit_dir}/{OBJECTS_DIR_NAME}` holds any object file, named by its oid.
+pub fn has_objects(ugit_dir: &Path) -> Result<bool> {
+    let objects_dir = ugit_dir.join(OBJECTS_DIR_NAME);
+
+    for entry in fs::read_dir(&objects_dir).with_path(&objects_dir)? {
+        let file_name = entry.with_path(&objects_dir)?.file_name();
+        if file_name
+            .as_encoded_bytes()
+            .iter()
+            .all(u8::is_ascii_hexdigit)
+        {
+            return Ok(true);
+        }
     }
+
+    Ok(false)
+}
+
+/// whether `ugit_dir` is an initialized repository dir.
+pub fn is_repo_dir(ugit_dir: &Path) -> bool {
+    is_dir_exist(ugit_dir.join(OBJECTS_DIR_NAME))
+}
+
+/// the nearest of `start` and its parent dirs which holds an initialized
+/// `{UGIT_REPOSITORY_NAME}`, [`None`] if there is none.
+pub fn discover_work_tree_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .find(|dir| is_repo_dir(&dir.join(UGIT_REPOSITORY_NAME)))
+        .map(Path::to_path_buf)
 }
 
 pub fn is_dir_exist<P: AsRef<Path>>(path: P) -> bool {
     Path::new(path.as_ref()).is_dir()
 }
 
-pub fn is_repo_dir_exist() -> bool {
-    is_dir_exist(UGIT_REPOSITORY_NAME)
+/// the dir of the user's ugit-rs files, like the global config and excludes files:
+/// `$XDG_CONFIG_HOME/ugit-rs`, or `$HOME/.config/ugit-rs`.
+pub fn user_config_dir() -> Option<PathBuf> {
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(config_home) if !config_home.is_empty() => Some(PathBuf::from(config_home)),
+        _ => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+    }
+    .map(|config_home| config_home.join("ugit-rs"))
 }
 
-pub fn is_objects_dir_exist() -> bool {
-    is_dir_exist(OBJECTS_DIR_PATH.deref())
+#[cfg(test)]
+mod tests {
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// a new empty dir in the temp dir.
+    fn tmp_dir() -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = env::temp_dir().join(format!(
+            "ugit-rs-dirs-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn the_nearest_work_tree_root_is_discovered() {
+        let root = tmp_dir();
+        let outer = root.join("outer");
+        let inner = outer.join("inner");
+        for work_tree in [&outer, &inner] {
+            let ugit_dir = work_tree.join(UGIT_REPOSITORY_NAME);
+            fs::create_dir_all(&ugit_dir).unwrap();
+            create_objects_dir(&ugit_dir).unwrap();
+        }
+        // not initialized, so it's walked past.
+        let uninitialized = inner.join("a");
+        fs::create_dir_all(uninitialized.join(UGIT_REPOSITORY_NAME)).unwrap();
+
+        assert_eq!(discover_work_tree_root(&outer), Some(outer.clone()));
+        assert_eq!(
+            discover_work_tree_root(&outer.join("x/y")),
+            Some(outer.clone())
+        );
+        assert_eq!(discover_work_tree_root(&inner), Some(inner.clone()));
+        assert_eq!(
+            discover_work_tree_root(&uninitialized.join("b")),
+            Some(inner.clone())
+        );
+        // the repository dir is in the work tree it belongs to.
+        assert_eq!(
+            discover_work_tree_root(&inner.join(UGIT_REPOSITORY_NAME).join(OBJECTS_DIR_NAME)),
+            Some(inner)
+        );
+        assert!(!is_repo_dir(&uninitialized.join(UGIT_REPOSITORY_NAME)));
+        assert_eq!(discover_work_tree_root(&root), None);
+
+        fs::remove_dir_all(root).unwrap();
+    }
 }
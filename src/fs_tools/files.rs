@@ -1,33 +1,271 @@
 use std::{
-    fs::{self, File},
-    io::{Read, Write},
+    ffi::OsStr,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, Read, Write},
+    os::unix::{self, ffi::OsStrExt, fs::PermissionsExt},
     path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
+use crate::error::{IoResultExt, Result};
+
+/// size of the buffer used when streaming file contents, the one hashing uses.
+pub use crate::crypto::STREAM_CHUNK_SIZE;
+
 pub fn is_file_exist<P: AsRef<Path>>(path: P) -> bool {
     Path::new(path.as_ref()).is_file()
 }
-pub fn read_content_to_end<P: AsRef<Path>>(path: P) -> Vec<u8> {
-    let mut file = File::open(&path).expect("could not open file");
+/// buffered reader of the file with given path.
+pub fn open_file<P: AsRef<Path>>(path: P) -> Result<BufReader<File>> {
+    let file = File::open(&path).with_path(&path)?;
+
+    Ok(BufReader::with_capacity(STREAM_CHUNK_SIZE, file))
+}
+
+/// target of the symlink with given path, as raw bytes.
+pub fn read_link_bytes<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
+    let target = fs::read_link(&path).with_path(&path)?;
+    Ok(target.as_os_str().as_bytes().to_vec())
+}
+
+/// a path in `dir` which no other temporary file of this process uses.
+fn tmp_path_in(dir: &Path, prefix: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    dir.join(format!(
+        "{}{}_{}",
+        prefix,
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ))
+}
 
-    let mut buf = vec![];
-    file.read_to_end(&mut buf).expect("could not read to end");
+/// the prefix of the temporary file a file at `path` is written through.
+fn tmp_prefix_for(path: &Path) -> String {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    format!(".{}.tmp_", file_name)
+}
+
+/// create the parent dir of `path` if it does not exist yet, return it.
+fn create_parent_dir(path: &Path) -> Result<&Path> {
+    let parent_dir = match path.parent() {
+        Some(parent_dir) if !parent_dir.as_os_str().is_empty() => parent_dir,
+        _ => Path::new("."),
+    };
 
-    buf
+    if !parent_dir.exists() {
+        fs::create_dir_all(parent_dir).with_path(parent_dir)?;
+    }
+
+    Ok(parent_dir)
 }
 
-pub fn store_file<P: AsRef<Path>>(path: P, bytes: &[u8]) {
-    let path = PathBuf::from(path.as_ref());
+/// a file written under a temporary name in the dir of its final path, and moved to
+/// that path by [`TempFile::persist`] once it's complete. so a crash midway never
+/// leaves a partly written file at the final path.
+///
+/// it's removed when dropped before being persisted, e.g. when writing it failed.
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf,
+    file: File,
+    persisted: bool,
+}
 
-    if let Some(parent_dir) = path.parent() {
-        if !parent_dir.exists() {
-            fs::create_dir_all(parent_dir).unwrap();
+impl TempFile {
+    /// create a new temporary file in `dir`, whose name starts with `prefix`.
+    pub fn new_in(dir: &Path, prefix: &str) -> Result<Self> {
+        let path = tmp_path_in(dir, prefix);
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .with_path(&path)?;
+
+        Ok(Self {
+            path,
+            file,
+            persisted: false,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// set the unix permission bits the file will have at its final path.
+    pub fn set_permissions(&self, mode: u32) -> Result<()> {
+        set_permissions(&self.path, mode)
+    }
+
+    /// fsync the content, then rename the file to `path`, replacing any file there.
+    pub fn persist<P: AsRef<Path>>(mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+
+        self.file.sync_all().with_path(&self.path)?;
+        fs::rename(&self.path, path).with_path(path)?;
+        self.persisted = true;
+
+        Ok(())
+    }
+}
+
+impl Write for TempFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            // nothing else refers to it, it's at most left behind.
+            let _ = fs::remove_file(&self.path);
         }
     }
+}
+
+/// create a symlink at `path` pointing to `target`, replacing any file already there.
+///
+/// the symlink is created under a temporary name and renamed, like [`TempFile`].
+pub fn store_symlink<P: AsRef<Path>>(path: P, target: &[u8]) -> Result<()> {
+    let path = path.as_ref();
+    let parent_dir = create_parent_dir(path)?;
 
-    let mut file = File::create(path).expect("create file fail");
+    let tmp_path = tmp_path_in(parent_dir, &tmp_prefix_for(path));
+    unix::fs::symlink(OsStr::from_bytes(target), &tmp_path).with_path(&tmp_path)?;
 
-    file.write_all(bytes).expect("write to file fail");
+    fs::rename(&tmp_path, path)
+        .with_path(path)
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&tmp_path);
+        })
+}
 
-    file.flush().expect("flush fail");
+/// set the unix permission bits of the file with given path.
+pub fn set_permissions<P: AsRef<Path>>(path: P, mode: u32) -> Result<()> {
+    fs::set_permissions(&path, fs::Permissions::from_mode(mode)).with_path(&path)
+}
+
+/// copy the content from `reader` into the file with given path, in chunks.
+/// the file gets the unix permission bits `mode`.
+///
+/// the content is written to a [`TempFile`] first, so `path` either keeps its
+/// old content or gets the whole new one.
+pub fn store_file<P: AsRef<Path>, R: Read>(path: P, reader: &mut R, mode: u32) -> Result<()> {
+    let path = path.as_ref();
+    let parent_dir = create_parent_dir(path)?;
+
+    let mut file = TempFile::new_in(parent_dir, &tmp_prefix_for(path))?;
+
+    io::copy(reader, &mut file).with_path(file.path())?;
+    file.set_permissions(mode)?;
+
+    file.persist(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    /// a new empty dir in the temp dir.
+    fn tmp_dir() -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = env::temp_dir().join(format!(
+            "ugit-rs-files-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    fn names_in(dir: &Path) -> Vec<PathBuf> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| PathBuf::from(entry.unwrap().file_name()))
+            .collect();
+        names.sort();
+
+        names
+    }
+
+    /// a reader which yields some content, then fails.
+    struct FailingReader(bool);
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0 {
+                return Err(io::Error::other("unreadable"));
+            }
+            self.0 = true;
+            buf[..7].copy_from_slice(b"partial");
+            Ok(7)
+        }
+    }
+
+    #[test]
+    fn a_dropped_temp_file_is_removed() {
+        let dir = tmp_dir();
+
+        let mut file = TempFile::new_in(&dir, ".f.tmp_").unwrap();
+        file.write_all(b"partial").unwrap();
+        assert!(file.path().exists());
+        drop(file);
+
+        assert!(names_in(&dir).is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_failed_store_keeps_the_old_file() {
+        let dir = tmp_dir();
+        let path = dir.join("f");
+
+        assert!(store_file(&path, &mut FailingReader(false), 0o644).is_err());
+        assert!(names_in(&dir).is_empty());
+
+        fs::write(&path, "old").unwrap();
+        assert!(store_file(&path, &mut FailingReader(false), 0o644).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"old");
+        assert_eq!(names_in(&dir), [PathBuf::from("f")]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stored_files_replace_existing_ones() {
+        let dir = tmp_dir();
+        let path = dir.join("sub/f");
+
+        store_file(&path, &mut &b"old"[..], 0o644).unwrap();
+        store_file(&path, &mut &b"new"[..], 0o755).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o755
+        );
+
+        // a symlink replaces a file, and a file replaces a symlink, without following it.
+        store_symlink(&path, b"target").unwrap();
+        assert_eq!(read_link_bytes(&path).unwrap(), b"target");
+        store_file(&path, &mut &b"file"[..], 0o644).unwrap();
+        assert!(!fs::symlink_metadata(&path).unwrap().is_symlink());
+        assert_eq!(fs::read(&path).unwrap(), b"file");
+        assert!(!dir.join("sub/target").exists());
+
+        assert_eq!(names_in(&dir.join("sub")), [PathBuf::from("f")]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
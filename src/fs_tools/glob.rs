@@ -0,0 +1,334 @@
+use std::collections::HashSet;
+
+/// match `text` against the glob `pattern`, in the way Git matches ignore patterns
+/// and pathspecs (its "wildmatch"):
+///
+/// - `*` matches anything except `/`, `?` matches any one char except `/`;
+/// - `[abc]`, `[a-z]` and `[!a-z]` (or `[^a-z]`) match one char of (or not of) a set,
+///   they never match `/`;
+/// - `\` escapes the next char;
+/// - `**` between slashes (or at the start or end of `pattern`) matches across `/`:
+///   `**/foo` matches `foo` in any dir, `foo/**` anything inside `foo`,
+///   and `a/**/b` matches `a/b`, `a/x/b`, `a/x/y/b`. any other `**` is a plain `*`.
+///
+/// both are raw bytes, so non-UTF-8 file names can be matched.
+pub fn wildmatch(pattern: &[u8], text: &[u8]) -> bool {
+    wildmatch_with(pattern, text, WildmatchFlags::default())
+}
+
+/// how [`wildmatch_with`] treats `/` and letter case.
+#[derive(Debug, Clone, Copy)]
+pub struct WildmatchFlags {
+    /// `*`, `?` and `[...]` never match `/`, and `**` is special. otherwise `/` is
+    /// an ordinary char, so `*.c` matches `src/main.c`.
+    pub pathname: bool,
+    /// ASCII letters match regardless of their case.
+    pub case_insensitive: bool,
+}
+
+impl Default for WildmatchFlags {
+    fn default() -> Self {
+        Self {
+            pathname: true,
+            case_insensitive: false,
+        }
+    }
+}
+
+/// [`wildmatch`] with the given `flags`.
+///
+/// takes `O(pattern.len() * text.len()²)` at worst, however many `*` the pattern has.
+pub fn wildmatch_with(pattern: &[u8], text: &[u8], flags: WildmatchFlags) -> bool {
+    Matcher {
+        pattern,
+        text,
+        flags,
+        failed: HashSet::new(),
+    }
+    .match_from(0, 0)
+}
+
+/// the state of a [`wildmatch_with`].
+struct Matcher<'a> {
+    pattern: &'a [u8],
+    text: &'a [u8],
+    flags: WildmatchFlags,
+    /// `(p, t)` of the [`Matcher::match_from`] which are known not to match.
+    /// without it, each `*` would retry the rest of the pattern at every position
+    /// for every position an earlier `*` is tried at, which is exponential.
+    failed: HashSet<(usize, usize)>,
+}
+
+impl Matcher<'_> {
+    /// whether `text[t..]` matches `pattern[p..]`.
+    fn match_from(&mut self, p: usize, t: usize) -> bool {
+        if self.failed.contains(&(p, t)) {
+            return false;
+        }
+
+        let matched = self.match_uncached(p, t);
+        if !matched {
+            self.failed.insert((p, t));
+        }
+
+        matched
+    }
+
+    /// whether `byte` may be matched by a wildcard.
+    fn is_wild(&self, byte: u8) -> bool {
+        !self.flags.pathname || byte != b'/'
+    }
+
+    fn eq(&self, a: u8, b: u8) -> bool {
+        match self.flags.case_insensitive {
+            true => a.eq_ignore_ascii_case(&b),
+            false => a == b,
+        }
+    }
+
+    /// [`Self::match_from`], `p` is kept to know what precedes a `**`.
+    fn match_uncached(&mut self, mut p: usize, mut t: usize) -> bool {
+        let (pattern, text) = (self.pattern, self.text);
+
+        while p < pattern.len() {
+            match pattern[p] {
+                b'*' => {
+                    let stars_start = p;
+                    while p < pattern.len() && pattern[p] == b'*' {
+                        p += 1;
+                    }
+
+                    let is_double_star = self.flags.pathname
+                        && p - stars_start >= 2
+                        && (stars_start == 0 || pattern[stars_start - 1] == b'/')
+                        && (p == pattern.len() || pattern[p] == b'/');
+
+                    if is_double_star {
+                        // `**` at the end matches everything left.
+                        if p == pattern.len() {
+                            return true;
+                        }
+
+                        // `**/rest` matches `rest` here, or after any `/` in the rest of `text`.
+                        let rest = p + 1;
+                        return self.match_from(rest, t)
+                            || (t..text.len())
+                                .any(|i| text[i] == b'/' && self.match_from(rest, i + 1));
+                    }
+
+                    // a single `*` never matches a `/`, unless `flags.pathname` is off.
+                    for i in t..=text.len() {
+                        if self.match_from(p, i) {
+                            return true;
+                        }
+                        if i < text.len() && !self.is_wild(text[i]) {
+                            break;
+                        }
+                    }
+
+                    return false;
+                }
+
+                b'?' => {
+                    if t >= text.len() || !self.is_wild(text[t]) {
+                        return false;
+                    }
+                    p += 1;
+                    t += 1;
+                }
+
+                b'[' => {
+                    if t >= text.len() || !self.is_wild(text[t]) {
+                        return false;
+                    }
+
+                    match match_class(pattern, p + 1, text[t], self.flags.case_insensitive) {
+                        Some((true, class_end)) => {
+                            p = class_end;
+                            t += 1;
+                        }
+                        Some((false, _)) => return false,
+                        // an unclosed `[` is a literal.
+                        None => {
+                            if text[t] != b'[' {
+                                return false;
+                            }
+                            p += 1;
+                            t += 1;
+                        }
+                    }
+                }
+
+                b'\\' if p + 1 < pattern.len() => {
+                    if t >= text.len() || !self.eq(text[t], pattern[p + 1]) {
+                        return false;
+                    }
+                    p += 2;
+                    t += 1;
+                }
+
+                literal => {
+                    if t >= text.len() || !self.eq(text[t], literal) {
+                        return false;
+                    }
+                    p += 1;
+                    t += 1;
+                }
+            }
+        }
+
+        t == text.len()
+    }
+}
+
+/// match `byte` against the class starting at `pattern[p..]` (just after its `[`).
+///
+/// Returns whether it matched and the position just after the closing `]`,
+/// or [`None`] if the class is never closed.
+fn match_class(
+    pattern: &[u8],
+    mut p: usize,
+    byte: u8,
+    case_insensitive: bool,
+) -> Option<(bool, usize)> {
+    let negated = matches!(pattern.get(p), Some(b'!') | Some(b'^'));
+    if negated {
+        p += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+
+    loop {
+        let mut low = *pattern.get(p)?;
+
+        // a `]` right after the `[` (or `[!`) is a literal.
+        if low == b']' && !first {
+            return Some((matched != negated, p + 1));
+        }
+        first = false;
+
+        if low == b'\\' {
+            p += 1;
+            low = *pattern.get(p)?;
+        }
+        p += 1;
+
+        let high = match (pattern.get(p), pattern.get(p + 1)) {
+            (Some(b'-'), Some(&high)) if high != b']' => {
+                p += 2;
+                if high == b'\\' {
+                    p += 1;
+                    *pattern.get(p - 1)?
+                } else {
+                    high
+                }
+            }
+            _ => low,
+        };
+
+        let in_range = |byte: u8| low <= byte && byte <= high;
+        if in_range(byte)
+            || (case_insensitive
+                && (in_range(byte.to_ascii_lowercase()) || in_range(byte.to_ascii_uppercase())))
+        {
+            matched = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        wildmatch(pattern.as_bytes(), text.as_bytes())
+    }
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*.c", "main.c"));
+        assert!(matches("*.c", ".c"));
+        assert!(!matches("*.c", "main.h"));
+        assert!(!matches("*.c", "src/main.c"));
+        assert!(matches("src/*.c", "src/main.c"));
+        assert!(matches("?.txt", "a.txt"));
+        assert!(!matches("?.txt", "ab.txt"));
+        assert!(!matches("a?b", "a/b"));
+        assert!(matches("foo", "foo"));
+        assert!(!matches("foo", "foobar"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(matches("[abc].txt", "b.txt"));
+        assert!(!matches("[abc].txt", "d.txt"));
+        assert!(matches("[a-z]1", "q1"));
+        assert!(!matches("[!a-z]1", "q1"));
+        assert!(matches("[^a-z]1", "Q1"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[!]]", "a"));
+        assert!(!matches("a[/]b", "a/b"));
+        // an unclosed `[` is a literal.
+        assert!(matches("a[b", "a[b"));
+    }
+
+    #[test]
+    fn escapes() {
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "a"));
+        assert!(matches("\\[a]", "[a]"));
+        assert!(matches("a\\?", "a?"));
+        assert!(!matches("a\\?", "ab"));
+    }
+
+    #[test]
+    fn double_stars() {
+        assert!(matches("**/foo", "foo"));
+        assert!(matches("**/foo", "a/b/foo"));
+        assert!(!matches("**/foo", "a/foobar"));
+        assert!(matches("foo/**", "foo/a"));
+        assert!(matches("foo/**", "foo/a/b"));
+        assert!(matches("a/**/b", "a/b"));
+        assert!(matches("a/**/b", "a/x/y/b"));
+        assert!(!matches("a/**/b", "a/x/c"));
+        // not between slashes, so it's a plain `*`.
+        assert!(matches("a**b", "axxb"));
+        assert!(!matches("a**b", "a/b"));
+    }
+
+    #[test]
+    fn flags() {
+        let no_pathname = WildmatchFlags {
+            pathname: false,
+            case_insensitive: false,
+        };
+        assert!(wildmatch_with(b"*.c", b"src/main.c", no_pathname));
+        assert!(wildmatch_with(b"a?b", b"a/b", no_pathname));
+
+        let case_insensitive = WildmatchFlags {
+            pathname: true,
+            case_insensitive: true,
+        };
+        assert!(wildmatch_with(b"*.TXT", b"notes.txt", case_insensitive));
+        assert!(wildmatch_with(b"[a-c]x", b"Bx", case_insensitive));
+        assert!(!wildmatch(b"*.TXT", b"notes.txt"));
+    }
+
+    #[test]
+    fn non_utf8_names() {
+        assert!(wildmatch(b"lat*", b"lat\xe9"));
+        assert!(wildmatch(b"\xe9?", b"\xe9\xff"));
+    }
+
+    #[test]
+    fn many_stars_are_not_exponential() {
+        let text = "a".repeat(200);
+        let pattern = format!("{}b", "*a".repeat(30));
+        assert!(!matches(&pattern, &text));
+
+        let pattern = "**/".repeat(30) + "b";
+        let text = "a/".repeat(100) + "c";
+        assert!(!matches(&pattern, &text));
+    }
+}
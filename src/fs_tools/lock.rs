@@ -0,0 +1,271 @@
+//! mutual exclusion of ugit-rs processes writing the same repository file.
+//!
+//! a process holding the lock of `{path}` has created `{path}{LOCK_SUFFIX}` exclusively
+//! (`O_EXCL`), and written its pid into it. any other process which tries to lock `{path}`
+//! fails with [`Error::Locked`], until the lock is released by dropping the [`LockFile`],
+//! which removes the lock file.
+//!
+//! a lock file left behind by a process which is not running anymore is stale. like Git,
+//! it's never removed automatically: the error tells its pid and path, so it can be removed
+//! by hand once it's sure no process uses it.
+//!
+//! objects need no locks, since an object file is only ever replaced by the same content.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+use crate::error::{Error, IoResultExt, Result};
+
+use super::files;
+
+pub const LOCK_SUFFIX: &str = ".lock";
+
+/// a lock whose holder can't be checked is stale after this long,
+/// no ugit-rs command holds a lock for that long.
+const STALE_LOCK_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// the lock of a repository file, which is released when dropped.
+#[derive(Debug)]
+pub struct LockFile {
+    /// the locked file.
+    path: PathBuf,
+    lock_path: PathBuf,
+    /// the lock file created by this process, kept open so its inode is not reused
+    /// while the lock is held.
+    file: File,
+}
+
+impl LockFile {
+    /// lock the file at `path`, which need not exist.
+    ///
+    /// fails with [`Error::Locked`] if another process holds the lock, or if it was left
+    /// behind by a process which is not running anymore.
+    pub fn acquire<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut lock_path = path.clone().into_os_string();
+        lock_path.push(LOCK_SUFFIX);
+        let lock_path = PathBuf::from(lock_path);
+
+        match create(&lock_path) {
+            Ok(file) => Ok(Self {
+                path,
+                lock_path,
+                file,
+            }),
+            Err(Error::Io { source, .. }) if source.kind() == io::ErrorKind::AlreadyExists => {
+                let pid = read_holder(&lock_path);
+                let stale = is_stale(&lock_path, pid);
+                Err(Error::Locked {
+                    lock_path,
+                    pid,
+                    stale,
+                })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// the locked file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// replace the content of the locked file with `content`, see [`files::store_file`].
+    pub fn write(&self, content: &[u8]) -> Result<()> {
+        files::store_file(&self.path, &mut Cursor::new(content), 0o644)
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        // the lock file may have been removed by hand, and created again by another process,
+        // whose lock must be kept.
+        let is_ours = match (fs::symlink_metadata(&self.lock_path), self.file.metadata()) {
+            (Ok(found), Ok(ours)) => (found.dev(), found.ino()) == (ours.dev(), ours.ino()),
+            _ => false,
+        };
+
+        // if it can't be removed, it's stale once this process exits.
+        if is_ours {
+            let _ = fs::remove_file(&self.lock_path);
+        }
+    }
+}
+
+/// create the lock file exclusively, and record the pid of this process in it.
+fn create(lock_path: &Path) -> Result<File> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(lock_path)
+        .with_path(lock_path)?;
+
+    let written = writeln!(file, "{}", process::id()).and_then(|_| file.sync_all());
+    if written.is_err() {
+        let _ = fs::remove_file(lock_path);
+    }
+
+    written.map(|_| file).with_path(lock_path)
+}
+
+/// the pid recorded in the lock file, [`None`] if it can't be read,
+/// e.g. its holder hasn't written it yet, or crashed before writing it.
+fn read_holder(lock_path: &Path) -> Option<u32> {
+    fs::read_to_string(lock_path).ok()?.trim().parse().ok()
+}
+
+/// whether the process with `pid` is running, [`None`] if it can't be checked.
+fn is_process_running(pid: u32) -> Option<bool> {
+    let proc_dir = Path::new("/proc");
+    if !proc_dir.join("self").exists() {
+        return None;
+    }
+
+    Some(proc_dir.join(pid.to_string()).exists())
+}
+
+/// whether the lock at `lock_path`, held by the process with `pid`, was left behind.
+///
+/// a lock without pid, or whose holder can't be checked, is stale once it's older
+/// than [`STALE_LOCK_AGE`].
+fn is_stale(lock_path: &Path, pid: Option<u32>) -> bool {
+    match pid.and_then(is_process_running) {
+        Some(running) => !running,
+        None => fs::metadata(lock_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > STALE_LOCK_AGE),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process::Command;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// a new empty dir in the temp dir.
+    fn tmp_dir() -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = env::temp_dir().join(format!(
+            "ugit-rs-lock-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    /// the pid of a process which has exited.
+    fn exited_pid() -> u32 {
+        let mut child = Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+
+        pid
+    }
+
+    fn assert_locked(path: &Path, expected_pid: Option<u32>, expected_stale: bool) {
+        match LockFile::acquire(path) {
+            Err(Error::Locked {
+                lock_path,
+                pid,
+                stale,
+            }) => {
+                assert_eq!(lock_path.file_name().unwrap(), "config.lock");
+                assert_eq!(pid, expected_pid);
+                assert_eq!(stale, expected_stale);
+            }
+            other => panic!("expected a held lock, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_held_lock_is_exclusive() {
+        let dir = tmp_dir();
+        let path = dir.join("config");
+        let lock_path = dir.join("config.lock");
+
+        let lock = LockFile::acquire(&path).unwrap();
+        assert_eq!(read_holder(&lock_path), Some(process::id()));
+        assert_locked(&path, Some(process::id()), false);
+
+        drop(lock);
+        assert!(!lock_path.exists());
+        drop(LockFile::acquire(&path).unwrap());
+        assert!(!lock_path.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_lock_without_pid_is_stale_once_it_is_old() {
+        let dir = tmp_dir();
+        let path = dir.join("config");
+        let lock_path = dir.join("config.lock");
+
+        // e.g. of a process which has not written its pid yet.
+        fs::write(&lock_path, "").unwrap();
+        assert_locked(&path, None, false);
+
+        let old = std::time::SystemTime::now() - STALE_LOCK_AGE * 2;
+        fs::File::options()
+            .write(true)
+            .open(&lock_path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        assert_locked(&path, None, true);
+        assert!(lock_path.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_stale_lock_is_reported_and_kept() {
+        let dir = tmp_dir();
+        let path = dir.join("config");
+        let lock_path = dir.join("config.lock");
+
+        let pid = exited_pid();
+        fs::write(&lock_path, format!("{}\n", pid)).unwrap();
+        assert_locked(&path, Some(pid), true);
+        assert_eq!(read_holder(&lock_path), Some(pid));
+
+        // once removed by hand, the file can be locked again.
+        fs::remove_file(&lock_path).unwrap();
+        let lock = LockFile::acquire(&path).unwrap();
+        lock.write(b"content").unwrap();
+        drop(lock);
+        assert_eq!(fs::read(&path).unwrap(), b"content");
+        assert!(!lock_path.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_lock_replaced_by_another_process_is_not_released() {
+        let dir = tmp_dir();
+        let path = dir.join("config");
+        let lock_path = dir.join("config.lock");
+
+        let lock = LockFile::acquire(&path).unwrap();
+        // removed by hand, and locked by another process.
+        fs::remove_file(&lock_path).unwrap();
+        fs::write(&lock_path, "1\n").unwrap();
+
+        drop(lock);
+        assert_eq!(read_holder(&lock_path), Some(1));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,3 +1,6 @@
 pub mod dirs;
 pub mod files;
+pub mod glob;
+pub mod lock;
 pub mod paths;
+pub mod pathspec;
@@ -1,25 +1,35 @@
 use std::{
     env, fs,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
 };
 
+use crate::error::{IoResultExt, Result};
+
 use super::dirs::UGIT_REPOSITORY_NAME;
 
-/// return the suffix of `to_check`, based on `base`.
-///
-/// `base` and `to_check` could be relative path.
-/// if so, the absolute path of both are compared.
+/// the absolute form of `path`, with `.` and `..` resolved lexically.
 ///
-/// Returns [`None`] if the `base` is not the parent of `to_check`.
-pub fn suffix_of<P1: AsRef<Path>, P2: AsRef<Path>>(base: P1, to_check: P2) -> Option<PathBuf> {
-    // TODO: error handle
-    let absolute_base = base.as_ref().canonicalize().unwrap();
-    let absolute_to_check = to_check.as_ref().canonicalize().unwrap();
-
-    match absolute_to_check.strip_prefix(absolute_base) {
-        Ok(suffix) => Some(suffix.into()),
-        Err(_) => None,
+/// unlike [`Path::canonicalize`], symlinks are not resolved and `path` need not exist.
+/// cwd is only needed, and may only fail to be read, if `path` is relative.
+pub fn normalize<P: AsRef<Path>>(path: P) -> Result<PathBuf> {
+    let path = path.as_ref();
+    let absolute = match path.is_absolute() {
+        true => path.to_path_buf(),
+        false => env::current_dir().with_path(".")?.join(path),
+    };
+    let mut result = PathBuf::new();
+
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            other => result.push(other),
+        }
     }
+
+    Ok(result)
 }
 
 pub fn is_parent_or_same_directory(parent: &Path, child: &Path) -> bool {
@@ -35,48 +45,51 @@ pub fn is_parent_or_same_directory(parent: &Path, child: &Path) -> bool {
     child_components.len() >= parent_components.len()
 }
 
-/// all files and directories in the current work directory will
-/// be deleted except the folder `UGIT_REPOSITORY_NAME`
-pub fn empty_cwd() {
-    let cwd = env::current_dir().unwrap();
+/// the names kept by [`empty_work_tree`] unless configured otherwise,
+/// see [`crate::config::READ_TREE_KEEP_KEY`].
+pub const DEFAULT_KEPT_NAMES: [&str; 2] = [".git", ".gitignore"];
+
+/// all files and directories in the work tree `dir` will be deleted except
+/// the folder `UGIT_REPOSITORY_NAME`, the repository dir `ugit_dir` whatever its name is,
+/// and the files named one of `kept_names`.
+pub fn empty_work_tree(dir: &Path, ugit_dir: &Path, kept_names: &[&str]) -> Result<()> {
+    let dir = normalize(dir)?;
 
     if cfg!(debug_assertions) {
         // preventing the deletion of the source code directory
         let project_root = env!("CARGO_MANIFEST_DIR");
         let project_root = PathBuf::from(project_root);
 
-        if is_parent_or_same_directory(&project_root, &cwd) {
-            // your cwd is under project root. careful.
+        if is_parent_or_same_directory(&project_root, &dir) {
+            // your work tree is under project root. careful.
 
-            println!("your current working directory is under project root. nothing was deleted. Be Careful.")
+            eprintln!("your work tree is under project root. nothing was deleted. Be Careful.")
         }
+
+        Ok(())
     } else {
-        delete_all_under(
-            &cwd,
-            // TODO
-            vec![UGIT_REPOSITORY_NAME, ".git", ".gitignore"],
-        );
+        let mut excludes = vec![UGIT_REPOSITORY_NAME];
+        excludes.extend(kept_names);
+
+        delete_all_under(&dir, excludes, &normalize(ugit_dir)?)
     }
 }
 
-fn delete_all_under(root: &Path, excludes: Vec<&str>) {
-    fs::read_dir(root)
-        .unwrap()
-        .map(|entry| entry.unwrap())
-        .filter(|entry| {
-            let binding = entry.path();
-            let entry_str = binding.as_path().as_os_str().to_str().unwrap();
-
-            excludes.iter().all(|exclude| !entry_str.contains(exclude))
-
-            // !excludes.contains(&e.path().as_path())
-        })
-        .for_each(|entry| {
-            let path = entry.path();
-            if entry.path().is_dir() {
-                fs::remove_dir_all(path).unwrap();
-            } else {
-                fs::remove_file(path).unwrap();
-            }
-        })
+fn delete_all_under(root: &Path, excludes: Vec<&str>, ugit_dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(root).with_path(root)? {
+        let entry = entry.with_path(root)?;
+        let path = entry.path();
+
+        if excludes.iter().any(|exclude| entry.file_name() == *exclude) || path == ugit_dir {
+            continue;
+        }
+
+        if path.is_dir() {
+            fs::remove_dir_all(&path).with_path(&path)?;
+        } else {
+            fs::remove_file(&path).with_path(&path)?;
+        }
+    }
+
+    Ok(())
 }
@@ -0,0 +1,474 @@
+use std::env;
+use std::ffi::OsStr;
+use std::fmt::{Display, Formatter};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Error, Result};
+
+use super::glob::{self, WildmatchFlags};
+use super::paths;
+
+/// makes every pathspec case-insensitive when set to `1`, like `:(icase)`.
+pub const ICASE_PATHSPECS_ENV: &str = "UGIT_ICASE_PATHSPECS";
+
+/// the chars which make a pathspec a glob, unless it's `:(literal)`.
+const GLOB_CHARS: &[u8] = b"*?[\\";
+
+/// one item of a [`Pathspec`], in the way Git parses pathspecs:
+///
+/// - `src/main.rs` matches the file itself, `src` every file under the dir;
+/// - `*.rs` is a glob matched against the whole path, where `*` also matches `/`,
+///   so it matches `src/main.rs` too;
+/// - magic is given by a leading `:(magic,...)` or the short forms `:!`, `:^` and `:/`:
+///   - `exclude` (`!`, `^`): paths matching the item are left out;
+///   - `icase`: letters match regardless of their case;
+///   - `literal`: `*`, `?` and `[` are plain chars;
+///   - `glob`: the item is a glob like in ignore files, `*` never matches `/`
+///     and `**` matches across dirs, see [`glob::wildmatch`];
+///   - `top` (`/`): the item is relative to the work tree root instead of cwd.
+#[derive(Debug, Clone)]
+pub struct PathspecItem {
+    /// the item as given.
+    origin: String,
+
+    /// relative to the work tree root.
+    pattern: Vec<u8>,
+    exclude: bool,
+    icase: bool,
+    literal: bool,
+    pathname_glob: bool,
+}
+
+impl Display for PathspecItem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.origin)
+    }
+}
+
+impl PathspecItem {
+    fn parse(spec: &OsStr, work_tree_root: &Path, icase_by_default: bool) -> Result<Self> {
+        let origin = spec.to_string_lossy().into_owned();
+        let mut rest = spec.as_bytes();
+
+        let mut exclude = false;
+        let mut icase = icase_by_default;
+        let mut literal = false;
+        let mut pathname_glob = false;
+        let mut top = false;
+
+        if let Some(after_colon) = rest.strip_prefix(b":") {
+            rest = after_colon;
+
+            if let Some(long_magic) = rest.strip_prefix(b"(") {
+                let close = match long_magic.iter().position(|&byte| byte == b')') {
+                    Some(close) => close,
+                    None => {
+                        return Err(Error::invalid_argument(format!(
+                            "missing ')' at the end of pathspec magic in '{}'",
+                            origin
+                        )))
+                    }
+                };
+
+                for word in long_magic[..close].split(|&byte| byte == b',') {
+                    match word {
+                        b"exclude" => exclude = true,
+                        b"icase" => icase = true,
+                        b"literal" => literal = true,
+                        b"glob" => pathname_glob = true,
+                        b"top" => top = true,
+                        _ => {
+                            return Err(Error::invalid_argument(format!(
+                                "invalid pathspec magic '{}' in '{}'",
+                                String::from_utf8_lossy(word),
+                                origin
+                            )))
+                        }
+                    }
+                }
+
+                rest = &long_magic[close + 1..];
+            } else {
+                while let Some((&magic, after_magic)) = rest.split_first() {
+                    match magic {
+                        b'!' | b'^' => exclude = true,
+                        b'/' => top = true,
+                        // an optional `:` ends the short magic.
+                        b':' => {
+                            rest = after_magic;
+                            break;
+                        }
+                        _ => break,
+                    }
+                    rest = after_magic;
+                }
+            }
+        }
+
+        if literal && pathname_glob {
+            return Err(Error::invalid_argument(format!(
+                "'literal' and 'glob' are incompatible in pathspec '{}'",
+                origin
+            )));
+        }
+
+        let path = Path::new(OsStr::from_bytes(rest));
+        let absolute = match top {
+            true => paths::normalize(work_tree_root.join(path))?,
+            false => paths::normalize(path)?,
+        };
+
+        let pattern = match absolute.strip_prefix(work_tree_root) {
+            Ok(relative) => relative.as_os_str().as_bytes().to_vec(),
+            Err(_) => {
+                return Err(Error::invalid_argument(format!(
+                    "'{}' is outside repository",
+                    origin
+                )))
+            }
+        };
+
+        Ok(Self {
+            origin,
+            pattern,
+            exclude,
+            icase,
+            literal,
+            pathname_glob,
+        })
+    }
+
+    fn is_glob(&self) -> bool {
+        !self.literal && self.pattern.iter().any(|byte| GLOB_CHARS.contains(byte))
+    }
+
+    /// the part of the pattern before its first glob char.
+    fn literal_prefix(&self) -> &[u8] {
+        match self.is_glob() {
+            true => {
+                let end = self
+                    .pattern
+                    .iter()
+                    .position(|byte| GLOB_CHARS.contains(byte))
+                    .unwrap_or(self.pattern.len());
+                &self.pattern[..end]
+            }
+            false => &self.pattern,
+        }
+    }
+
+    fn matches_exactly(&self, relative_path: &[u8]) -> bool {
+        let literal_match = match self.icase {
+            true => self.pattern.eq_ignore_ascii_case(relative_path),
+            false => self.pattern == relative_path,
+        };
+
+        literal_match
+            || (self.is_glob()
+                && glob::wildmatch_with(
+                    &self.pattern,
+                    relative_path,
+                    WildmatchFlags {
+                        pathname: self.pathname_glob,
+                        case_insensitive: self.icase,
+                    },
+                ))
+    }
+
+    /// whether the item matches `relative_path` (relative to the work tree root),
+    /// or one of its dirs.
+    pub fn matches(&self, relative_path: &Path) -> bool {
+        // the empty pattern is the work tree root, which holds everything.
+        self.pattern.is_empty()
+            || relative_path
+                .ancestors()
+                .take_while(|path| !path.as_os_str().is_empty())
+                .any(|path| self.matches_exactly(path.as_os_str().as_bytes()))
+    }
+
+    /// whether files under `relative_dir` may match the item.
+    fn may_match_under(&self, relative_dir: &Path) -> bool {
+        let mut dir = relative_dir.as_os_str().as_bytes().to_vec();
+        if !dir.is_empty() {
+            dir.push(b'/');
+        }
+
+        // a literal item must be under the dir, a glob may match from its prefix on.
+        let prefix = self.literal_prefix();
+        let common_len = match self.is_glob() {
+            true => prefix.len().min(dir.len()),
+            false if prefix.len() >= dir.len() => dir.len(),
+            false => return false,
+        };
+
+        match self.icase {
+            true => prefix[..common_len].eq_ignore_ascii_case(&dir[..common_len]),
+            false => prefix[..common_len] == dir[..common_len],
+        }
+    }
+
+    pub fn is_exclude(&self) -> bool {
+        self.exclude
+    }
+}
+
+/// selects the paths of a work tree a command works on, see [`PathspecItem`].
+///
+/// a path is selected if it matches any item which is not an exclude, and none of
+/// the exclude items. an empty pathspec (or one with only exclude items) selects
+/// every path not excluded.
+#[derive(Debug, Clone)]
+pub struct Pathspec {
+    /// normalized, see [`paths::normalize`].
+    work_tree_root: PathBuf,
+    items: Vec<PathspecItem>,
+}
+
+impl Pathspec {
+    /// parse `specs`, given relative to cwd.
+    ///
+    /// all items are case-insensitive if `{ICASE_PATHSPECS_ENV}` is set to `1`.
+    pub fn new<S: AsRef<OsStr>, P: AsRef<Path>>(specs: &[S], work_tree_root: P) -> Result<Self> {
+        let icase_by_default = env::var_os(ICASE_PATHSPECS_ENV).is_some_and(|value| value == "1");
+
+        Self::parse(specs, work_tree_root, icase_by_default)
+    }
+
+    fn parse<S: AsRef<OsStr>, P: AsRef<Path>>(
+        specs: &[S],
+        work_tree_root: P,
+        icase_by_default: bool,
+    ) -> Result<Self> {
+        let work_tree_root = paths::normalize(work_tree_root)?;
+        let items = specs
+            .iter()
+            .map(|spec| PathspecItem::parse(spec.as_ref(), &work_tree_root, icase_by_default))
+            .collect::<Result<_>>()?;
+
+        Ok(Self {
+            work_tree_root,
+            items,
+        })
+    }
+
+    /// selects every path of the work tree.
+    pub fn all<P: AsRef<Path>>(work_tree_root: P) -> Result<Self> {
+        Self::new::<&str, _>(&[], work_tree_root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn items(&self) -> &[PathspecItem] {
+        &self.items
+    }
+
+    /// `path` relative to the work tree root, [`None`] if it's outside the work tree
+    /// (or it's relative and cwd is gone).
+    pub fn relative_path<P: AsRef<Path>>(&self, path: P) -> Option<PathBuf> {
+        paths::normalize(path)
+            .ok()?
+            .strip_prefix(&self.work_tree_root)
+            .ok()
+            .map(Path::to_path_buf)
+    }
+
+    /// whether the file at `path` is selected.
+    pub fn matches<P: AsRef<Path>>(&self, path: P) -> bool {
+        let relative_path = match self.relative_path(path) {
+            Some(relative_path) => relative_path,
+            None => return false,
+        };
+
+        let (excludes, includes): (Vec<_>, Vec<_>) =
+            self.items.iter().partition(|item| item.is_exclude());
+
+        (includes.is_empty() || includes.iter().any(|item| item.matches(&relative_path)))
+            && !excludes.iter().any(|item| item.matches(&relative_path))
+    }
+
+    /// whether any file under the dir at `path` may be selected,
+    /// so walking into it can be skipped otherwise.
+    pub fn may_match_under<P: AsRef<Path>>(&self, path: P) -> bool {
+        let relative_dir = match self.relative_path(path) {
+            Some(relative_dir) => relative_dir,
+            None => return false,
+        };
+
+        let (excludes, includes): (Vec<_>, Vec<_>) =
+            self.items.iter().partition(|item| item.is_exclude());
+
+        (includes.is_empty()
+            || includes
+                .iter()
+                .any(|item| item.matches(&relative_dir) || item.may_match_under(&relative_dir)))
+            && !excludes.iter().any(|item| item.matches(&relative_dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROOT: &str = "/work";
+
+    fn pathspec(specs: &[&str]) -> Pathspec {
+        Pathspec::parse(specs, ROOT, false).unwrap()
+    }
+
+    fn item(spec: &str) -> PathspecItem {
+        pathspec(&[spec]).items[0].clone()
+    }
+
+    fn parse_err(spec: &str) -> bool {
+        matches!(
+            Pathspec::parse(&[spec], ROOT, false),
+            Err(Error::InvalidArgument(_))
+        )
+    }
+
+    fn matches(specs: &[&str], path: &str) -> bool {
+        pathspec(specs).matches(Path::new(ROOT).join(path))
+    }
+
+    fn may_match_under(specs: &[&str], dir: &str) -> bool {
+        pathspec(specs).may_match_under(Path::new(ROOT).join(dir))
+    }
+
+    #[test]
+    fn plain_items_match_files_and_dirs() {
+        assert!(matches(&["/work/src"], "src"));
+        assert!(matches(&["/work/src"], "src/a/main.rs"));
+        assert!(!matches(&["/work/src"], "srcs/main.rs"));
+        assert!(!matches(&["/work/src"], "SRC/main.rs"));
+        assert!(matches(&["/work"], "any/file"));
+        assert!(!pathspec(&["/work"]).matches("/other/file"));
+
+        // relative to cwd.
+        let cwd = env::current_dir().unwrap();
+        let relative = Pathspec::parse(&["src/../src/fs_tools"], &cwd, false).unwrap();
+        assert!(relative.matches(cwd.join("src/fs_tools/pathspec.rs")));
+        assert!(!relative.matches(cwd.join("src/objects")));
+    }
+
+    #[test]
+    fn globs() {
+        // `*` matches `/`, unless it's `:(glob)`.
+        assert!(matches(&["/work/*.rs"], "main.rs"));
+        assert!(matches(&["/work/*.rs"], "src/main.rs"));
+        assert!(matches(&[":(glob)/work/*.rs"], "main.rs"));
+        assert!(!matches(&[":(glob)/work/*.rs"], "src/main.rs"));
+        assert!(matches(&[":(glob)/work/**/main.rs"], "a/b/main.rs"));
+        assert!(matches(&["/work/src/[ab].rs"], "src/a.rs"));
+        assert!(!matches(&["/work/src/[ab].rs"], "src/c.rs"));
+
+        assert!(matches(&[":(literal)/work/*.rs"], "*.rs"));
+        assert!(!matches(&[":(literal)/work/*.rs"], "main.rs"));
+        assert!(!item(":(literal)/work/*.rs").is_glob());
+    }
+
+    #[test]
+    fn long_magic() {
+        let exclude = item(":(exclude)/work/target");
+        assert!(exclude.is_exclude());
+        assert_eq!(exclude.pattern, b"target");
+        assert_eq!(exclude.to_string(), ":(exclude)/work/target");
+
+        assert!(matches(&[":(icase)/work/SRC"], "src/main.rs"));
+        assert!(matches(&[":(icase)/work/*.RS"], "src/main.rs"));
+        assert!(!matches(&["/work/*.RS"], "src/main.rs"));
+
+        assert_eq!(item(":(top)src").pattern, b"src");
+        assert_eq!(item(":(top)a/../b").pattern, b"b");
+        assert!(item(":(top)").pattern.is_empty());
+
+        let combined = item(":(exclude,icase,top)Target");
+        assert!(combined.is_exclude() && combined.icase);
+        assert_eq!(combined.pattern, b"Target");
+    }
+
+    #[test]
+    fn short_magic() {
+        assert!(item(":!:/work/a").is_exclude());
+        assert!(item(":^:/work/a").is_exclude());
+        assert_eq!(item(":/src").pattern, b"src");
+        assert_eq!(item(":!/src").pattern, b"src");
+        assert_eq!(item(":!:/work/src").pattern, b"src");
+        assert!(item(":/").pattern.is_empty());
+
+        // a `/` after `!` is the short form of `top`, not the root of an absolute path.
+        assert_eq!(item(":!/work/a").pattern, b"work/a");
+        assert_eq!(item("/work/:a").pattern, b":a");
+    }
+
+    #[test]
+    fn invalid_magic_is_rejected() {
+        assert!(parse_err(":(bogus)src"));
+        assert!(parse_err(":(top,bogus)src"));
+        assert!(parse_err(":()src"));
+        assert!(parse_err(":(top"));
+        assert!(parse_err(":(literal,glob)src"));
+        assert!(parse_err(":(top)../outside"));
+        assert!(parse_err("/outside"));
+    }
+
+    #[test]
+    fn exclude_only_pathspecs() {
+        let excludes = [":!:/work/target", ":^:/work/*.log"];
+
+        assert!(matches(&excludes, "src/main.rs"));
+        assert!(!matches(&excludes, "target"));
+        assert!(!matches(&excludes, "target/debug/app"));
+        assert!(!matches(&excludes, "logs/today.log"));
+
+        assert!(may_match_under(&excludes, "src"));
+        assert!(!may_match_under(&excludes, "target"));
+        assert!(!may_match_under(&excludes, "target/debug"));
+
+        // an exclude narrows the items which are not excludes.
+        let narrowed = ["/work/src", ":(exclude)/work/src/gen"];
+        assert!(matches(&narrowed, "src/main.rs"));
+        assert!(!matches(&narrowed, "src/gen/parser.rs"));
+        assert!(!matches(&narrowed, "docs/a.md"));
+    }
+
+    #[test]
+    fn dirs_are_pruned() {
+        let literal = ["/work/src/lib/a.rs"];
+        assert!(may_match_under(&literal, ""));
+        assert!(may_match_under(&literal, "src"));
+        assert!(may_match_under(&literal, "src/lib"));
+        assert!(!may_match_under(&literal, "src/li"));
+        assert!(!may_match_under(&literal, "src/lib/deeper"));
+        assert!(!may_match_under(&literal, "docs"));
+        // a dir matched by the item as a whole.
+        assert!(may_match_under(&["/work/src"], "src/lib/deeper"));
+
+        let glob = ["/work/src/*.rs"];
+        assert!(may_match_under(&glob, "src"));
+        assert!(may_match_under(&glob, "src/any/depth"));
+        assert!(!may_match_under(&glob, "docs"));
+        assert!(may_match_under(&["/work/s*"], "src"));
+
+        assert!(may_match_under(&[":(icase)/work/SRC/a.rs"], "src"));
+        assert!(!may_match_under(&["/work/SRC/a.rs"], "src"));
+
+        assert!(!pathspec(&literal).may_match_under("/other"));
+        assert!(pathspec(&[]).may_match_under(ROOT));
+    }
+
+    #[test]
+    fn icase_pathspecs_override() {
+        // the only test which reads the variable, so the others are not affected.
+        env::set_var(ICASE_PATHSPECS_ENV, "1");
+        let icase = Pathspec::new(&["/work/SRC"], ROOT);
+        env::set_var(ICASE_PATHSPECS_ENV, "0");
+        let case_sensitive = Pathspec::new(&["/work/SRC"], ROOT);
+        env::remove_var(ICASE_PATHSPECS_ENV);
+
+        assert!(icase.unwrap().matches("/work/src/main.rs"));
+        assert!(!case_sensitive.unwrap().matches("/work/src/main.rs"));
+    }
+}
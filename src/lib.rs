@@ -0,0 +1,14 @@
+//! ugit-rs, a tiny Git written to learn more about how Git works on the inside.
+//!
+//! the object database lives in [`objects`], and [`Repository`] ties it to a
+//! repository dir and its work tree. the `ugit-rs` binary is a thin CLI on top of it.
+
+pub mod config;
+pub mod crypto;
+pub mod error;
+pub mod fs_tools;
+pub mod objects;
+pub mod repository;
+
+pub use error::{Error, Result};
+pub use repository::{InitOptions, Repository};
@@ -1,11 +1,7 @@
-#[macro_use]
-mod unexpected;
-
 mod cli;
-mod crypto;
-mod fs_tools;
-mod objects;
 
-fn main() {
-    cli::run();
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    cli::run()
 }
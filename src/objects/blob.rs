@@ -1,33 +1,186 @@
+use crate::crypto::ObjectId;
+use crate::error::{IoResultExt, Result};
 use crate::fs_tools::files;
-use crate::objects::ObjectVirtualContent;
+use crate::objects::chunked::{self, ChunkManifest};
+use crate::objects::db::insert::ObjectInsert;
+use crate::objects::db::query;
+use crate::objects::db::store::StoreRef;
+use crate::objects::type_literal::ObjectTypeLiteral;
+use crate::objects::{compute_oid, ObjectFileContent, ObjectVirtualContent, OidComputable};
 use std::fmt::Debug;
-use std::path::Path;
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
 
-/// origin dir could be restored by `TreeObject`(stored in `TreeVitrualFileEntry`).
+/// origin dir could be restored by `TreeObject`(stored in `TreeVirtualFileEntry`).
 /// so, `BlobObject` does not need to hold `ObjectDBPath`.
+///
+/// the origin content is never held in memory as a whole: it is read in chunks
+/// from its source whenever it is hashed, stored or restored.
+///
+/// a large file may be stored as a manifest object plus chunk blobs (see [`chunked`]),
+/// which is transparent to the users of `BlobObject`.
 #[derive(Clone, Debug)]
 pub struct BlobObject {
-    origin_content: Vec<u8>,
+    /// the object store this blob is read from and inserted into.
+    store: StoreRef,
+    source: BlobSource,
+}
+
+/// where the origin content of a `BlobObject` is read from.
+#[derive(Clone, Debug)]
+enum BlobSource {
+    /// a file in the work dir.
+    OriginFile(PathBuf),
+    /// a blob object which is already in the database.
+    Stored(ObjectId),
+    /// a manifest object which is already in the database.
+    StoredManifest(ObjectId),
+    /// small content held in memory, e.g. the target of a symlink.
+    Memory(Vec<u8>),
 }
 
 impl BlobObject {
-    /// origin file content -> Self
-    pub fn new(origin_content: Vec<u8>) -> Self {
-        Self { origin_content }
+    /// origin file path -> Self
+    pub fn from_origin_path<P: AsRef<Path>>(store: StoreRef, origin_path: P) -> Self {
+        Self {
+            store,
+            source: BlobSource::OriginFile(origin_path.as_ref().to_path_buf()),
+        }
     }
 
-    pub fn origin_content(&self) -> &Vec<u8> {
-        &self.origin_content
+    /// symlink path -> Self, whose content is the link target.
+    pub fn from_symlink<P: AsRef<Path>>(store: StoreRef, symlink_path: P) -> Result<Self> {
+        Ok(Self {
+            store,
+            source: BlobSource::Memory(files::read_link_bytes(symlink_path)?),
+        })
     }
 
-    /// origin file path -> Self
-    pub fn from_origin_path<P: AsRef<Path>>(origin_path: P) -> Self {
-        Self::new(files::read_content_to_end(origin_path.as_ref()))
+    /// content held in memory -> Self
+    pub fn from_content(store: StoreRef, content: Vec<u8>) -> Self {
+        Self {
+            store,
+            source: BlobSource::Memory(content),
+        }
+    }
+
+    /// oid of a blob object in the database -> Self
+    pub fn from_stored_oid(store: StoreRef, oid: ObjectId) -> Self {
+        Self {
+            store,
+            source: BlobSource::Stored(oid),
+        }
+    }
+
+    /// oid of a manifest object in the database -> Self
+    pub fn from_stored_manifest_oid(store: StoreRef, oid: ObjectId) -> Self {
+        Self {
+            store,
+            source: BlobSource::StoredManifest(oid),
+        }
+    }
+
+    /// whether the blob is (or will be) stored as a manifest plus chunk blobs.
+    pub fn is_chunked(&self) -> bool {
+        match &self.source {
+            BlobSource::OriginFile(path) => chunked::should_chunk(self.store.as_ref(), path),
+            BlobSource::Stored(_) | BlobSource::Memory(_) => false,
+            BlobSource::StoredManifest(_) => true,
+        }
+    }
+
+    /// the whole origin content, read into memory.
+    pub fn origin_content(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        self.origin_reader()?.read_to_end(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    /// the size of the origin content.
+    ///
+    /// a chunked blob sums the chunk sizes of its manifest, no chunk is read.
+    /// a blob stored as a single object is counted while it's streamed.
+    pub fn size(&self) -> Result<u64> {
+        Ok(match &self.source {
+            BlobSource::OriginFile(path) => fs::metadata(path).with_path(path)?.len(),
+            BlobSource::Stored(_) => io::copy(&mut self.origin_reader()?, &mut io::sink())?,
+            BlobSource::StoredManifest(oid) => {
+                ChunkManifest::from_manifest_obj_oid(self.store.as_ref(), oid)?.size()
+            }
+            BlobSource::Memory(content) => content.len() as u64,
+        })
+    }
+
+    /// streaming reader of the origin content.
+    pub fn origin_reader(&self) -> Result<Box<dyn Read>> {
+        Ok(match &self.source {
+            BlobSource::OriginFile(path) => Box::new(files::open_file(path)?),
+            BlobSource::Stored(oid) => query::open_object_file(self.store.as_ref(), oid)?.1,
+            BlobSource::StoredManifest(oid) => Box::new(
+                ChunkManifest::from_manifest_obj_oid(self.store.as_ref(), oid)?
+                    .origin_reader(self.store.clone()),
+            ),
+            BlobSource::Memory(content) => Box::new(Cursor::new(content.clone())),
+        })
     }
 }
 
 impl ObjectVirtualContent for BlobObject {
-    fn obj_virtual_content(&self) -> Vec<u8> {
-        self.origin_content.clone()
+    fn obj_virtual_content(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        self.obj_virtual_reader()?.read_to_end(&mut buf)?;
+
+        Ok(buf)
+    }
+
+    /// the origin content, or the manifest content for a chunked blob.
+    fn obj_virtual_reader(&self) -> Result<Box<dyn Read + '_>> {
+        Ok(match &self.source {
+            BlobSource::OriginFile(_) if self.is_chunked() => {
+                let manifest =
+                    ChunkManifest::compute(self.origin_reader()?, self.store.hash_algorithm())?;
+                Box::new(Cursor::new(manifest.to_string().into_bytes()))
+            }
+            BlobSource::OriginFile(_) | BlobSource::Memory(_) => self.origin_reader()?,
+            BlobSource::Stored(oid) | BlobSource::StoredManifest(oid) => {
+                query::open_object_file(self.store.as_ref(), oid)?.1
+            }
+        })
+    }
+}
+
+impl OidComputable for BlobObject {
+    fn oid(&self) -> Result<ObjectId> {
+        match &self.source {
+            BlobSource::OriginFile(_) | BlobSource::Memory(_) => {
+                compute_oid(self, self.store.hash_algorithm())
+            }
+            BlobSource::Stored(oid) | BlobSource::StoredManifest(oid) => Ok(*oid),
+        }
+    }
+}
+
+impl ObjectInsert for BlobObject {
+    fn insert_into_db(&self) -> Result<ObjectId> {
+        match &self.source {
+            BlobSource::OriginFile(_) if self.is_chunked() => {
+                // chunks are stored while the manifest is built, reading the origin file once.
+                let manifest = ChunkManifest::store(self.origin_reader()?, self.store.as_ref())?;
+
+                let mut obj_file_content = vec![];
+                obj_file_content.extend(ObjectTypeLiteral::Manifest.to_string().as_bytes());
+                obj_file_content.push(super::TYPE_CONTENT_SEPARATOR);
+                obj_file_content.extend(manifest.to_string().as_bytes());
+
+                self.store.write(&mut Cursor::new(obj_file_content))
+            }
+            BlobSource::OriginFile(_) | BlobSource::Memory(_) => {
+                self.store.write(&mut self.obj_file_reader()?)
+            }
+            // already in the database.
+            BlobSource::Stored(oid) | BlobSource::StoredManifest(oid) => Ok(*oid),
+        }
     }
 }
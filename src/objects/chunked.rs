@@ -0,0 +1,463 @@
+//! Content-defined chunking of large blobs.
+//!
+//! when enabled at `init --chunked-blobs`, files of at least [`CHUNKED_BLOB_THRESHOLD`] bytes
+//! are not stored as a single blob object. their content is split by a rolling hash into
+//! chunks, each chunk is stored as an ordinary blob object, and a *manifest object* lists
+//! the chunks in order. the oid of the manifest is what trees refer to.
+//!
+//! chunk boundaries only depend on the bytes around them, so a small edit to a large file
+//! only changes the chunks around the edit, and every other chunk is deduplicated.
+//!
+//! # Manifest Virtual Content
+//!
+//! `{chunk oid} {chunk size}` per line, for example:
+//!
+//! ```plaintext
+//! 2a4f1c6c3a0e9d1b8f65cbb4c93be2e1fd06bb4c 1048907
+//! 7c0d1a52d96e60a0b21f09cbc7d2bbca34b8b0d9 734211
+//! ```
+
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::mem;
+use std::path::Path;
+
+use crate::crypto::{HashAlgorithm, ObjectId};
+use crate::error::{Error, Result};
+use crate::objects::db::query;
+use crate::objects::db::store::{ObjectStore, StoreRef};
+use crate::objects::type_literal::ObjectTypeLiteral;
+use crate::objects::TYPE_CONTENT_SEPARATOR;
+
+pub const MANIFEST_ENTRY_SEPARATE_STRING: &str = "\n";
+
+/// files smaller than this are always stored as a single blob object.
+pub const CHUNKED_BLOB_THRESHOLD: u64 = 8 * 1024 * 1024;
+
+/// no chunk (except the last one) is smaller than this.
+pub const MIN_CHUNK_SIZE: usize = 256 * 1024;
+/// no chunk is larger than this.
+pub const MAX_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+/// a boundary is placed where the rolling hash has these bits cleared,
+/// giving chunks of about 1 MiB on average.
+const BOUNDARY_MASK: u64 = (1 << 20) - 1;
+
+/// random values for the gear rolling hash, generated with splitmix64 from a fixed seed.
+/// they must never change, or the chunk boundaries of existing files would move.
+const GEAR: [u64; 256] = gear_table();
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0x75_67_69_74_2d_72_73;
+
+    let mut i = 0;
+    while i < table.len() {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        table[i] = z ^ (z >> 31);
+
+        i += 1;
+    }
+
+    table
+}
+
+/// whether the origin file with given path should be stored as chunks in `store`.
+pub fn should_chunk<P: AsRef<Path>>(store: &dyn ObjectStore, origin_path: P) -> bool {
+    store.chunked_blobs()
+        && fs::metadata(origin_path)
+            .map(|metadata| metadata.len() >= CHUNKED_BLOB_THRESHOLD)
+            .unwrap_or(false)
+}
+
+/// the length of the first chunk of `data`.
+///
+/// `data` must either hold at least [`MAX_CHUNK_SIZE`] bytes, or be the rest of the content.
+fn cut_point(data: &[u8]) -> usize {
+    if data.len() <= MIN_CHUNK_SIZE {
+        return data.len();
+    }
+
+    let end = data.len().min(MAX_CHUNK_SIZE);
+    let mut hash: u64 = 0;
+
+    for (i, &byte) in data.iter().enumerate().take(end).skip(MIN_CHUNK_SIZE) {
+        hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+
+        if hash & BOUNDARY_MASK == 0 {
+            return i + 1;
+        }
+    }
+
+    end
+}
+
+/// splits the content of a reader into content-defined chunks.
+///
+/// at most [`MAX_CHUNK_SIZE`] bytes are buffered at a time.
+pub struct Chunker<R> {
+    reader: R,
+    buf: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> Chunker<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buf: Vec::with_capacity(MAX_CHUNK_SIZE),
+            eof: false,
+        }
+    }
+
+    fn fill_buf(&mut self) -> io::Result<()> {
+        while !self.eof && self.buf.len() < MAX_CHUNK_SIZE {
+            let wanted = (MAX_CHUNK_SIZE - self.buf.len()) as u64;
+
+            if (&mut self.reader).take(wanted).read_to_end(&mut self.buf)? == 0 {
+                self.eof = true;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for Chunker<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Err(e) = self.fill_buf() {
+            return Some(Err(e));
+        }
+
+        if self.buf.is_empty() {
+            return None;
+        }
+
+        let rest = self.buf.split_off(cut_point(&self.buf));
+        Some(Ok(mem::replace(&mut self.buf, rest)))
+    }
+}
+
+/// the object file content of a chunk, which is an ordinary blob object.
+fn chunk_obj_file_reader(chunk: &[u8]) -> impl Read + '_ {
+    let mut header = vec![];
+
+    header.extend(ObjectTypeLiteral::Blob.to_string().as_bytes());
+    header.push(TYPE_CONTENT_SEPARATOR);
+
+    Cursor::new(header).chain(chunk)
+}
+
+/// the ordered list of chunks a large file was split into.
+#[derive(Clone, Debug, Default)]
+pub struct ChunkManifest {
+    /// `(chunk oid, chunk size)`
+    chunks: Vec<(ObjectId, u64)>,
+}
+
+impl ChunkManifest {
+    /// chunk the content of `reader` and hash each chunk, without storing anything.
+    pub fn compute<R: Read>(reader: R, hash_algorithm: HashAlgorithm) -> Result<Self> {
+        Self::_from_reader(reader, |chunk| {
+            Ok(hash_algorithm.digest_reader(chunk_obj_file_reader(chunk))?)
+        })
+    }
+
+    /// chunk the content of `reader` and store each chunk into `store`.
+    pub fn store<R: Read>(reader: R, store: &dyn ObjectStore) -> Result<Self> {
+        Self::_from_reader(reader, |chunk| {
+            store.write(&mut chunk_obj_file_reader(chunk))
+        })
+    }
+
+    fn _from_reader<R: Read, F: FnMut(&[u8]) -> Result<ObjectId>>(
+        reader: R,
+        mut chunk_oid: F,
+    ) -> Result<Self> {
+        let chunks = Chunker::new(reader)
+            .map(|chunk| {
+                let chunk = chunk?;
+                Ok((chunk_oid(&chunk)?, chunk.len() as u64))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { chunks })
+    }
+
+    pub fn from_obj_content(
+        manifest_obj_content: Vec<u8>,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<Self> {
+        // manifest obj content must be UTF-8 chars.
+        let manifest_obj_content = match String::from_utf8(manifest_obj_content) {
+            Ok(manifest_obj_content) => manifest_obj_content,
+            Err(_) => return Err(Error::corrupt_object("manifest is not UTF-8")),
+        };
+
+        let chunks = manifest_obj_content
+            .split(MANIFEST_ENTRY_SEPARATE_STRING)
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let (oid, size) = match line.split_once(' ') {
+                    Some(fields) => fields,
+                    None => {
+                        return Err(Error::corrupt_object("unrecognized manifest entry format"))
+                    }
+                };
+
+                let oid = match ObjectId::from_hex(oid, hash_algorithm) {
+                    Ok(oid) => oid,
+                    Err(e) => {
+                        return Err(Error::corrupt_object(format!(
+                            "unrecognized manifest entry oid: {}",
+                            e
+                        )))
+                    }
+                };
+
+                let size = match size.parse() {
+                    Ok(size) => size,
+                    Err(_) => {
+                        return Err(Error::corrupt_object("unrecognized manifest entry size"))
+                    }
+                };
+
+                Ok((oid, size))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { chunks })
+    }
+
+    pub fn from_manifest_obj_oid(store: &dyn ObjectStore, manifest_oid: &ObjectId) -> Result<Self> {
+        let (obj_literal, obj_content_after_type) = query::read_object_file(store, manifest_oid)?;
+
+        if obj_literal != ObjectTypeLiteral::Manifest {
+            return Err(Error::TypeMismatch {
+                oid: *manifest_oid,
+                expected: ObjectTypeLiteral::Manifest,
+                actual: obj_literal,
+            });
+        }
+
+        Self::from_obj_content(obj_content_after_type, store.hash_algorithm())
+            .map_err(|e| e.in_object(manifest_oid))
+    }
+
+    /// the size of the origin content, the sum of the chunk sizes.
+    pub fn size(&self) -> u64 {
+        self.chunks.iter().map(|(_, size)| size).sum()
+    }
+
+    /// streaming reader of the origin content, reading one chunk blob at a time.
+    pub fn origin_reader(&self, store: StoreRef) -> ChunkedReader {
+        ChunkedReader {
+            store,
+            chunks: self
+                .chunks
+                .iter()
+                .map(|(oid, _)| *oid)
+                .collect::<Vec<_>>()
+                .into_iter(),
+            current: None,
+        }
+    }
+}
+
+impl Display for ChunkManifest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (oid, size) in &self.chunks {
+            write!(f, "{} {}{}", oid, size, MANIFEST_ENTRY_SEPARATE_STRING)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// concatenation of the chunk blobs listed in a [`ChunkManifest`].
+pub struct ChunkedReader {
+    store: StoreRef,
+    chunks: std::vec::IntoIter<ObjectId>,
+    current: Option<Box<dyn Read>>,
+}
+
+impl Read for ChunkedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let current = match &mut self.current {
+                Some(current) => current,
+                None => match self.chunks.next() {
+                    Some(oid) => {
+                        let (obj_literal, reader) =
+                            query::open_object_file(self.store.as_ref(), &oid)
+                                .map_err(Error::into_io)?;
+
+                        if obj_literal != ObjectTypeLiteral::Blob {
+                            return Err(Error::TypeMismatch {
+                                oid,
+                                expected: ObjectTypeLiteral::Blob,
+                                actual: obj_literal,
+                            }
+                            .into_io());
+                        }
+
+                        self.current.insert(reader)
+                    }
+                    None => return Ok(0),
+                },
+            };
+
+            match current.read(buf)? {
+                0 => self.current = None,
+                n => return Ok(n),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::objects::blob::BlobObject;
+    use crate::objects::db::memory::MemoryObjectStore;
+
+    /// `len` pseudo-random bytes, the same for the same `seed`.
+    fn random_bytes(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed | 1;
+
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn chunks_of(data: &[u8]) -> Vec<Vec<u8>> {
+        Chunker::new(data)
+            .collect::<io::Result<_>>()
+            .expect("reading from memory never fails")
+    }
+
+    #[test]
+    fn small_content_is_one_chunk() {
+        assert_eq!(cut_point(&[]), 0);
+        assert_eq!(cut_point(&vec![1; MIN_CHUNK_SIZE]), MIN_CHUNK_SIZE);
+
+        let data = random_bytes(MIN_CHUNK_SIZE - 1, 1);
+        assert_eq!(chunks_of(&data), vec![data]);
+    }
+
+    #[test]
+    fn chunk_sizes_are_clamped() {
+        let data = random_bytes(12 * 1024 * 1024, 2);
+        let chunks = chunks_of(&data);
+
+        let (last, rest) = chunks.split_last().unwrap();
+        assert!(!rest.is_empty());
+        for chunk in rest {
+            assert!((MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&chunk.len()));
+        }
+        assert!(!last.is_empty() && last.len() <= MAX_CHUNK_SIZE);
+
+        assert_eq!(chunks.concat(), data);
+    }
+
+    #[test]
+    fn content_without_boundary_is_cut_at_max_size() {
+        // the rolling hash of a run of the same byte settles, so it never hits a boundary
+        // unless it does within the first 64 bytes.
+        let byte = (0..=u8::MAX)
+            .find(|&byte| cut_point(&vec![byte; MAX_CHUNK_SIZE]) == MAX_CHUNK_SIZE)
+            .unwrap();
+        let data = vec![byte; 2 * MAX_CHUNK_SIZE + 10];
+
+        let sizes: Vec<_> = chunks_of(&data).iter().map(Vec::len).collect();
+        assert_eq!(sizes, vec![MAX_CHUNK_SIZE, MAX_CHUNK_SIZE, 10]);
+    }
+
+    /// the chunks of `edited` which are not chunks of `origin`.
+    fn new_chunks(origin: &[u8], edited: &[u8]) -> usize {
+        let origin_chunks: HashSet<_> = chunks_of(origin).into_iter().collect();
+
+        chunks_of(edited)
+            .iter()
+            .filter(|chunk| !origin_chunks.contains(*chunk))
+            .count()
+    }
+
+    #[test]
+    fn small_edits_only_change_nearby_chunks() {
+        let origin = random_bytes(16 * 1024 * 1024, 3);
+        let middle = origin.len() / 2;
+
+        let mut overwritten = origin.clone();
+        overwritten[middle..middle + 100].fill(0);
+        assert!(new_chunks(&origin, &overwritten) <= 2);
+
+        let mut inserted = origin.clone();
+        inserted.splice(middle..middle, [7; 100]);
+        assert!(new_chunks(&origin, &inserted) <= 2);
+
+        let mut removed = origin.clone();
+        removed.drain(middle..middle + 100);
+        assert!(new_chunks(&origin, &removed) <= 2);
+    }
+
+    #[test]
+    fn manifest_content_round_trips() {
+        let data = random_bytes(3 * 1024 * 1024, 4);
+        let manifest = ChunkManifest::compute(&data[..], HashAlgorithm::Sha1).unwrap();
+
+        let parsed =
+            ChunkManifest::from_obj_content(manifest.to_string().into_bytes(), HashAlgorithm::Sha1)
+                .unwrap();
+        assert_eq!(parsed.chunks, manifest.chunks);
+        assert_eq!(parsed.size(), data.len() as u64);
+
+        assert!(
+            ChunkManifest::from_obj_content(b"not a manifest\n".to_vec(), HashAlgorithm::Sha1)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn manifest_blob_round_trips() {
+        let store: StoreRef = Arc::new(MemoryObjectStore::new(HashAlgorithm::Sha256));
+        let data = random_bytes(5 * 1024 * 1024, 5);
+
+        let manifest = ChunkManifest::store(&data[..], store.as_ref()).unwrap();
+        assert!(manifest.chunks.len() > 1);
+        for (oid, _) in &manifest.chunks {
+            assert!(store.has(oid));
+        }
+
+        // the same chunks are computed without storing them.
+        let computed = ChunkManifest::compute(&data[..], HashAlgorithm::Sha256).unwrap();
+        assert_eq!(computed.chunks, manifest.chunks);
+
+        let mut obj_file_content = ObjectTypeLiteral::Manifest.to_string().into_bytes();
+        obj_file_content.push(TYPE_CONTENT_SEPARATOR);
+        obj_file_content.extend(manifest.to_string().as_bytes());
+        let manifest_oid = store.write(&mut Cursor::new(obj_file_content)).unwrap();
+
+        let blob = BlobObject::from_stored_manifest_oid(store.clone(), manifest_oid);
+        assert_eq!(blob.size().unwrap(), data.len() as u64);
+        assert_eq!(blob.origin_content().unwrap(), data);
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufWriter, Read, Write},
+    path::PathBuf,
+};
+
+use flate2::{bufread::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::{
+    crypto::{HashAlgorithm, ObjectId},
+    error::{Error, IoResultExt, Result},
+    fs_tools::files::{self, TempFile},
+};
+
+use super::store::ObjectStore;
+
+/// the name prefix of the temporary files objects are written to.
+const TMP_OBJECT_PREFIX: &str = "tmp_obj_";
+
+/// the first byte of a zlib stream with the default window size.
+const ZLIB_HEADER_BYTE: u8 = 0x78;
+
+/// stores each object zlib-compressed in its own file `{objects_dir}/{oid}`.
+#[derive(Debug, Clone)]
+pub struct LooseObjectStore {
+    objects_dir: PathBuf,
+    hash_algorithm: HashAlgorithm,
+    chunked_blobs: bool,
+}
+
+impl LooseObjectStore {
+    pub fn new(objects_dir: PathBuf, hash_algorithm: HashAlgorithm) -> Self {
+        Self {
+            objects_dir,
+            hash_algorithm,
+            chunked_blobs: false,
+        }
+    }
+
+    /// store large files as chunks, see [`crate::objects::chunked`].
+    pub fn with_chunked_blobs(mut self, chunked_blobs: bool) -> Self {
+        self.chunked_blobs = chunked_blobs;
+        self
+    }
+
+    #[inline]
+    pub fn object_save_path_for_oid(&self, oid: &ObjectId) -> PathBuf {
+        self.objects_dir.join(oid.to_string())
+    }
+}
+
+impl ObjectStore for LooseObjectStore {
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    fn chunked_blobs(&self) -> bool {
+        self.chunked_blobs
+    }
+
+    fn has(&self, oid: &ObjectId) -> bool {
+        files::is_file_exist(self.object_save_path_for_oid(oid))
+    }
+
+    /// object files written before objects were compressed are still readable.
+    fn read(&self, oid: &ObjectId) -> Result<Box<dyn Read>> {
+        let path = self.object_save_path_for_oid(oid);
+        let mut file = match files::open_file(&path) {
+            Ok(file) => file,
+            Err(Error::Io { source, .. }) if source.kind() == io::ErrorKind::NotFound => {
+                return Err(Error::MissingObject(*oid))
+            }
+            Err(e) => return Err(e),
+        };
+
+        let is_compressed = file.fill_buf().with_path(&path)?.first() == Some(&ZLIB_HEADER_BYTE);
+
+        if is_compressed {
+            Ok(Box::new(ZlibDecoder::new(file)))
+        } else {
+            Ok(Box::new(file))
+        }
+    }
+
+    /// hash and compress the object file content in chunks of [`files::STREAM_CHUNK_SIZE`].
+    ///
+    /// since the oid is only known after the last chunk, the compressed content is
+    /// first written to a [`TempFile`] in the objects dir, and renamed afterwards.
+    /// so an object file is either complete or missing, never truncated.
+    ///
+    /// an object which is already stored is not rewritten, the temporary file is dropped.
+    fn write(&self, reader: &mut dyn Read) -> Result<ObjectId> {
+        let file = TempFile::new_in(&self.objects_dir, TMP_OBJECT_PREFIX)?;
+        let tmp_path = file.path().to_path_buf();
+
+        let mut encoder = ZlibEncoder::new(
+            BufWriter::with_capacity(files::STREAM_CHUNK_SIZE, file),
+            Compression::default(),
+        );
+        let mut hasher = self.hash_algorithm.hasher();
+        let mut buf = vec![0; files::STREAM_CHUNK_SIZE];
+
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            hasher.update(&buf[..n]);
+            encoder.write_all(&buf[..n]).with_path(&tmp_path)?;
+        }
+
+        let file = encoder
+            .finish()
+            .with_path(&tmp_path)?
+            .into_inner()
+            .map_err(|e| e.into_error())
+            .with_path(&tmp_path)?;
+
+        let oid = hasher.finalize();
+
+        if !self.has(&oid) {
+            file.persist(self.object_save_path_for_oid(&oid))?;
+        }
+
+        Ok(oid)
+    }
+
+    fn iterate(&self) -> Result<Box<dyn Iterator<Item = ObjectId> + '_>> {
+        let entries = fs::read_dir(&self.objects_dir).with_path(&self.objects_dir)?;
+
+        Ok(Box::new(entries.filter_map(move |entry| {
+            let file_name = entry.ok()?.file_name();
+            ObjectId::from_hex(file_name.to_str()?, self.hash_algorithm).ok()
+        })))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::os::unix::fs::MetadataExt;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// a new store in an empty dir in the temp dir.
+    fn store() -> LooseObjectStore {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let objects_dir = env::temp_dir().join(format!(
+            "ugit-rs-loose-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&objects_dir).unwrap();
+
+        LooseObjectStore::new(objects_dir, HashAlgorithm::Sha1)
+    }
+
+    fn file_names(store: &LooseObjectStore) -> Vec<String> {
+        fs::read_dir(&store.objects_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect()
+    }
+
+    /// a reader which yields some content, then fails.
+    struct FailingReader(bool);
+
+    impl Read for FailingReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0 {
+                return Err(io::Error::other("unreadable"));
+            }
+            self.0 = true;
+            buf[..10].copy_from_slice(b"blob\0parti");
+            Ok(10)
+        }
+    }
+
+    #[test]
+    fn a_failed_write_leaves_nothing() {
+        let store = store();
+
+        assert!(store.write(&mut FailingReader(false)).is_err());
+        assert!(file_names(&store).is_empty());
+
+        fs::remove_dir_all(&store.objects_dir).unwrap();
+    }
+
+    #[test]
+    fn objects_are_written_once() {
+        let store = store();
+
+        let oid = store.write(&mut &b"blob\0hello"[..]).unwrap();
+        assert_eq!(file_names(&store), [oid.to_string()]);
+        let path = store.object_save_path_for_oid(&oid);
+        let inode = fs::metadata(&path).unwrap().ino();
+
+        // not renamed over, and the temporary file is removed.
+        assert_eq!(store.write(&mut &b"blob\0hello"[..]).unwrap(), oid);
+        assert_eq!(fs::metadata(&path).unwrap().ino(), inode);
+        assert_eq!(file_names(&store), [oid.to_string()]);
+
+        let mut content = vec![];
+        store.read(&oid).unwrap().read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"blob\0hello");
+
+        fs::remove_dir_all(&store.objects_dir).unwrap();
+    }
+}
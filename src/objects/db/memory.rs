@@ -0,0 +1,232 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{Debug, Formatter},
+    io::{Cursor, Read},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::crypto::{HashAlgorithm, ObjectId};
+use crate::error::{Error, Result};
+
+use super::store::ObjectStore;
+
+/// keeps objects in memory, so the object database can be used without touching disk,
+/// e.g. by tests and tools.
+#[derive(Default)]
+pub struct MemoryObjectStore {
+    hash_algorithm: HashAlgorithm,
+    objects: Mutex<BTreeMap<ObjectId, Arc<[u8]>>>,
+}
+
+impl MemoryObjectStore {
+    pub fn new(hash_algorithm: HashAlgorithm) -> Self {
+        Self {
+            hash_algorithm,
+            objects: Mutex::default(),
+        }
+    }
+
+    /// the objects, which are never left half written by a panicking thread.
+    fn objects(&self) -> MutexGuard<'_, BTreeMap<ObjectId, Arc<[u8]>>> {
+        self.objects
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Debug for MemoryObjectStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryObjectStore")
+            .field("hash_algorithm", &self.hash_algorithm)
+            .field("objects", &self.objects().keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ObjectStore for MemoryObjectStore {
+    fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    fn has(&self, oid: &ObjectId) -> bool {
+        self.objects().contains_key(oid)
+    }
+
+    fn read(&self, oid: &ObjectId) -> Result<Box<dyn Read>> {
+        match self.objects().get(oid) {
+            Some(content) => Ok(Box::new(Cursor::new(content.clone()))),
+            None => Err(Error::MissingObject(*oid)),
+        }
+    }
+
+    fn write(&self, reader: &mut dyn Read) -> Result<ObjectId> {
+        let mut content = vec![];
+        reader.read_to_end(&mut content)?;
+
+        let oid = self.hash_algorithm.digest(&content);
+        self.objects().entry(oid).or_insert_with(|| content.into());
+
+        Ok(oid)
+    }
+
+    fn iterate(&self) -> Result<Box<dyn Iterator<Item = ObjectId> + '_>> {
+        Ok(Box::new(
+            self.objects()
+                .keys()
+                .copied()
+                .collect::<Vec<_>>()
+                .into_iter(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::objects::blob::BlobObject;
+    use crate::objects::db::insert::ObjectInsert;
+    use crate::objects::db::store::StoreRef;
+    use crate::objects::file_mode::FileMode;
+    use crate::objects::tree::TreeObject;
+    use crate::objects::tree_entry::TreeVirtualFileEntry;
+    use crate::objects::type_literal::ObjectTypeLiteral;
+    use crate::objects::{Object, OidComputable, TYPE_CONTENT_SEPARATOR};
+
+    fn store(hash_algorithm: HashAlgorithm) -> StoreRef {
+        Arc::new(MemoryObjectStore::new(hash_algorithm))
+    }
+
+    /// store the tree with `entries` as it's encoded, return its oid.
+    fn write_tree(store: &StoreRef, entries: &[TreeVirtualFileEntry]) -> ObjectId {
+        let mut obj_file_content = ObjectTypeLiteral::Tree.to_string().into_bytes();
+        obj_file_content.push(TYPE_CONTENT_SEPARATOR);
+        obj_file_content.extend(entries.iter().flat_map(TreeVirtualFileEntry::to_bytes));
+
+        store.write(&mut Cursor::new(obj_file_content)).unwrap()
+    }
+
+    #[test]
+    fn objects_round_trip() {
+        let store = store(HashAlgorithm::Sha1);
+        let oid = store.write(&mut &b"blob\0hello"[..]).unwrap();
+
+        // the oid of the object file content, like Git's `blob 5\0hello` but without the size.
+        assert_eq!(oid, HashAlgorithm::Sha1.digest(b"blob\0hello"));
+        assert!(store.has(&oid));
+
+        let mut content = vec![];
+        store.read(&oid).unwrap().read_to_end(&mut content).unwrap();
+        assert_eq!(content, b"blob\0hello");
+
+        // writing it again stores nothing new.
+        assert_eq!(store.write(&mut &b"blob\0hello"[..]).unwrap(), oid);
+        assert_eq!(store.iterate().unwrap().collect::<Vec<_>>(), vec![oid]);
+    }
+
+    #[test]
+    fn missing_object() {
+        let store = store(HashAlgorithm::Sha256);
+        let oid = HashAlgorithm::Sha256.digest(b"blob\0missing");
+
+        assert!(!store.has(&oid));
+        assert!(matches!(store.read(&oid), Err(Error::MissingObject(missing)) if missing == oid));
+        assert!(matches!(
+            Object::restore_from_file_with_oid(store, oid),
+            Err(Error::MissingObject(_))
+        ));
+    }
+
+    #[test]
+    fn blob_round_trips() {
+        let store = store(HashAlgorithm::Sha256);
+        let blob = BlobObject::from_content(store.clone(), b"some content\n".to_vec());
+
+        let oid = blob.insert_into_db().unwrap();
+        assert_eq!(oid, blob.oid().unwrap());
+
+        match Object::restore_from_file_with_oid(store, oid).unwrap() {
+            Object::BlobObject(restored) => {
+                assert_eq!(restored.oid().unwrap(), oid);
+                assert_eq!(restored.size().unwrap(), 13);
+                assert_eq!(restored.origin_content().unwrap(), b"some content\n");
+            }
+            Object::TreeObject(_) => panic!("a blob is restored as a tree"),
+        }
+    }
+
+    #[test]
+    fn tree_round_trips() {
+        let store = store(HashAlgorithm::Sha1);
+        let file_oid = BlobObject::from_content(store.clone(), b"a file".to_vec())
+            .insert_into_db()
+            .unwrap();
+        let script_oid = BlobObject::from_content(store.clone(), b"#!/bin/sh".to_vec())
+            .insert_into_db()
+            .unwrap();
+
+        let sub_oid = write_tree(
+            &store,
+            &[TreeVirtualFileEntry::new(
+                FileMode::Executable,
+                script_oid,
+                PathBuf::from("build.sh"),
+            )],
+        );
+        let root_oid = write_tree(
+            &store,
+            &[
+                TreeVirtualFileEntry::new(FileMode::Regular, file_oid, PathBuf::from("a.txt")),
+                TreeVirtualFileEntry::new(FileMode::Directory, sub_oid, PathBuf::from("sub")),
+            ],
+        );
+
+        let tree = TreeObject::from_tree_obj_oid(store.clone(), root_oid).unwrap();
+        assert_eq!(tree.oid().unwrap(), root_oid);
+
+        let entries: Vec<_> = tree
+            .entries()
+            .unwrap()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                format!("100644 blob {}\ta.txt", file_oid),
+                format!("040000 tree {}\tsub", sub_oid),
+            ]
+        );
+
+        let files: Vec<_> = tree
+            .files()
+            .into_iter()
+            .map(|(path, mode, blob)| (path, mode, blob.origin_content().unwrap()))
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                (
+                    PathBuf::from("a.txt"),
+                    FileMode::Regular,
+                    b"a file".to_vec()
+                ),
+                (
+                    PathBuf::from("sub/build.sh"),
+                    FileMode::Executable,
+                    b"#!/bin/sh".to_vec()
+                ),
+            ]
+        );
+
+        // inserting a tree which is read from the store stores nothing new.
+        assert_eq!(tree.insert_into_db().unwrap(), root_oid);
+        assert_eq!(store.iterate().unwrap().count(), 4);
+
+        assert!(matches!(
+            TreeObject::from_tree_obj_oid(store, file_oid),
+            Err(Error::TypeMismatch { .. })
+        ));
+    }
+}
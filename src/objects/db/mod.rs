@@ -1,14 +1,6 @@
-use std::{ops::Deref, path::PathBuf};
-
-use crate::fs_tools::dirs;
-
 pub mod insert;
+pub mod loose;
+pub mod memory;
 pub mod query;
 pub mod restore;
-
-pub type OID = String;
-
-#[inline]
-pub fn object_save_path_for_oid(oid: &OID) -> PathBuf {
-    PathBuf::from(format!("{}/{}", &dirs::OBJECTS_DIR_PATH.deref(), oid))
-}
+pub mod store;
@@ -1,38 +1,79 @@
+use std::io::{self, BufRead, BufReader, Read};
+
 use crate::{
-    fs_tools::files,
+    crypto::ObjectId,
+    error::{Error, Result},
     objects::{type_literal::ObjectTypeLiteral, TYPE_CONTENT_SEPARATOR},
 };
 
-use super::{object_save_path_for_oid, OID};
+use super::store::ObjectStore;
 
-pub trait ObjectQuery {
-    fn query_db_with_oid(oid: OID) -> Self;
+/// an error reading the object with `oid`, where a broken zlib stream means it's corrupt.
+fn read_error(oid: &ObjectId, e: io::Error) -> Error {
+    match e.kind() {
+        io::ErrorKind::InvalidData | io::ErrorKind::InvalidInput => Error::CorruptObject {
+            oid: Some(*oid),
+            reason: e.to_string(),
+        },
+        _ => e.into(),
+    }
 }
 
 /// return `(type, obj file content after type literal)`
-pub fn read_object_file(oid: &OID) -> (ObjectTypeLiteral, Vec<u8>) {
-    let obj_file_content = files::read_content_to_end(object_save_path_for_oid(oid));
-
-    if let Some(sep_idx) = obj_file_content
-        .iter()
-        .position(|&x| x == TYPE_CONTENT_SEPARATOR)
-    {
-        let (type_literal, mut origin_contents) = obj_file_content.split_at(sep_idx);
-
-        let type_literal = match ObjectTypeLiteral::try_from(
-            String::from_utf8(type_literal.to_vec()).unwrap().as_str(),
-        ) {
-            Ok(type_literal) => type_literal,
-            Err(_) => {
-                panic!("unknown obj type literal")
-            }
-        };
-
-        // skip `0x00`
-        origin_contents = &origin_contents[1..];
-
-        (type_literal, origin_contents.to_vec())
-    } else {
-        panic!("unrecognized object file format.")
+pub fn read_object_file(
+    store: &dyn ObjectStore,
+    oid: &ObjectId,
+) -> Result<(ObjectTypeLiteral, Vec<u8>)> {
+    let (type_literal, mut reader) = open_object_file(store, oid)?;
+
+    let mut origin_contents = vec![];
+    reader.read_to_end(&mut origin_contents)?;
+
+    Ok((type_literal, origin_contents))
+}
+
+/// return `(type, streaming reader of obj file content after type literal)`
+pub fn open_object_file(
+    store: &dyn ObjectStore,
+    oid: &ObjectId,
+) -> Result<(ObjectTypeLiteral, Box<dyn Read>)> {
+    let mut reader = BufReader::new(store.read(oid)?);
+
+    let mut type_literal = vec![];
+    reader
+        .read_until(TYPE_CONTENT_SEPARATOR, &mut type_literal)
+        .map_err(|e| read_error(oid, e))?;
+
+    if type_literal.pop() != Some(TYPE_CONTENT_SEPARATOR) {
+        return Err(Error::corrupt_object("unrecognized object file format").in_object(oid));
+    }
+
+    let type_literal = std::str::from_utf8(&type_literal)
+        .ok()
+        .and_then(|type_literal| ObjectTypeLiteral::try_from(type_literal).ok());
+
+    match type_literal {
+        Some(type_literal) => Ok((
+            type_literal,
+            Box::new(ObjectReader {
+                oid: *oid,
+                inner: reader,
+            }),
+        )),
+        None => Err(Error::corrupt_object("unknown obj type literal").in_object(oid)),
+    }
+}
+
+/// the content of an object, whose read errors are [`read_error`]s.
+struct ObjectReader<R> {
+    oid: ObjectId,
+    inner: R,
+}
+
+impl<R: Read> Read for ObjectReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner
+            .read(buf)
+            .map_err(|e| read_error(&self.oid, e).into_io())
     }
 }
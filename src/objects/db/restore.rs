@@ -1,3 +1,10 @@
+use std::path::Path;
+
+use crate::error::Result;
+
 pub trait ObjectRestore {
-    fn restore(&self);
+    /// restore the object into the work tree `dir`, whose repository dir is `ugit_dir`.
+    ///
+    /// the files in `dir` named one of `kept_names` are left alone.
+    fn restore(&self, dir: &Path, ugit_dir: &Path, kept_names: &[&str]) -> Result<()>;
 }
@@ -0,0 +1,39 @@
+use std::fmt::Debug;
+use std::io::Read;
+use std::sync::Arc;
+
+use crate::crypto::{HashAlgorithm, ObjectId};
+use crate::error::Result;
+
+/// shared handle of an object store, held by the objects which live in it.
+///
+/// it may be sent to and shared with other threads, so a [`crate::Repository`] can be.
+pub type StoreRef = Arc<dyn ObjectStore + Send + Sync>;
+
+/// backend which persists *object file contents* by their oid.
+///
+/// objects only talk to the database through this trait, so they could live
+/// in the `.ugit-rs/objects` dir ([`super::loose::LooseObjectStore`]),
+/// or in memory ([`super::memory::MemoryObjectStore`]).
+pub trait ObjectStore: Debug {
+    /// the hash algorithm used to compute the oids of this store.
+    fn hash_algorithm(&self) -> HashAlgorithm;
+
+    /// whether large files are stored as chunks, see [`crate::objects::chunked`].
+    fn chunked_blobs(&self) -> bool {
+        false
+    }
+
+    fn has(&self, oid: &ObjectId) -> bool;
+
+    /// streaming reader of the object file content with given oid.
+    ///
+    /// fails with [`crate::Error::MissingObject`] if there is no such object.
+    fn read(&self, oid: &ObjectId) -> Result<Box<dyn Read>>;
+
+    /// hash and store the object file content yielded by `reader`, return its oid.
+    fn write(&self, reader: &mut dyn Read) -> Result<ObjectId>;
+
+    /// oids of all objects in this store.
+    fn iterate(&self) -> Result<Box<dyn Iterator<Item = ObjectId> + '_>>;
+}
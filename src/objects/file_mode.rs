@@ -0,0 +1,97 @@
+use crate::objects::type_literal::ObjectTypeLiteral;
+use std::fmt::{Display, Formatter};
+use std::fs::Metadata;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+/// the kind of a tree entry, recorded as an octal mode the way Git does.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FileMode {
+    /// `100644`
+    Regular,
+    /// `100755`, any of the executable bits of the origin file is set.
+    Executable,
+    /// `120000`, the blob holds the link target.
+    Symlink,
+    /// `040000`
+    Directory,
+}
+
+impl FileMode {
+    /// mode of a file in the work dir, [`None`] for a FIFO, a socket or a device,
+    /// which can't be stored. reading a FIFO would block until something writes to it.
+    ///
+    /// `metadata` must come from [`std::fs::symlink_metadata`], so symlinks are not followed.
+    pub fn from_metadata(metadata: &Metadata) -> Option<Self> {
+        let file_type = metadata.file_type();
+
+        if file_type.is_fifo()
+            || file_type.is_socket()
+            || file_type.is_block_device()
+            || file_type.is_char_device()
+        {
+            None
+        } else if file_type.is_symlink() {
+            Some(FileMode::Symlink)
+        } else if file_type.is_dir() {
+            Some(FileMode::Directory)
+        } else if is_executable(metadata) {
+            Some(FileMode::Executable)
+        } else {
+            Some(FileMode::Regular)
+        }
+    }
+
+    /// the type of the object a tree entry with this mode refers to: a tree for a dir,
+    /// a blob otherwise, also for a file stored as chunks.
+    pub fn obj_type(&self) -> ObjectTypeLiteral {
+        match self {
+            FileMode::Directory => ObjectTypeLiteral::Tree,
+            FileMode::Regular | FileMode::Executable | FileMode::Symlink => ObjectTypeLiteral::Blob,
+        }
+    }
+
+    /// the default mode of an entry written before modes were recorded.
+    pub fn default_for(obj_type: ObjectTypeLiteral) -> Self {
+        match obj_type {
+            ObjectTypeLiteral::Tree => FileMode::Directory,
+            ObjectTypeLiteral::Blob | ObjectTypeLiteral::Manifest => FileMode::Regular,
+        }
+    }
+
+    /// permission bits a restored file with this mode gets.
+    pub fn permissions(&self) -> u32 {
+        match self {
+            FileMode::Executable | FileMode::Directory => 0o755,
+            FileMode::Regular | FileMode::Symlink => 0o644,
+        }
+    }
+}
+
+fn is_executable(metadata: &Metadata) -> bool {
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+impl Display for FileMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileMode::Regular => write!(f, "100644"),
+            FileMode::Executable => write!(f, "100755"),
+            FileMode::Symlink => write!(f, "120000"),
+            FileMode::Directory => write!(f, "040000"),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for FileMode {
+    type Error = ();
+
+    fn try_from(value: &'a str) -> Result<Self, Self::Error> {
+        match value {
+            "100644" => Ok(Self::Regular),
+            "100755" => Ok(Self::Executable),
+            "120000" => Ok(Self::Symlink),
+            "040000" | "40000" => Ok(Self::Directory),
+            _ => Err(()),
+        }
+    }
+}
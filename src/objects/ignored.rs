@@ -1,15 +1,562 @@
-use crate::fs_tools::dirs::UGIT_REPOSITORY_NAME;
-use std::path::Path;
+use crate::config::{Config, EXCLUDES_FILE_KEY};
+use crate::error::{IoResultExt, Result};
+use crate::fs_tools::dirs::{self, INFO_EXCLUDE_FILE_NAME, UGIT_REPOSITORY_NAME};
+use crate::fs_tools::{glob, paths};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
 
-pub fn is_ignored<P: AsRef<Path>>(origin_path: P) -> bool {
-    let excludes = vec![UGIT_REPOSITORY_NAME, "target", ".git"];
+/// the per-directory ignore file, its patterns are relative to the dir it's in.
+pub const IGNORE_FILE_NAME: &str = ".ugitignore";
 
-    let origin_path = origin_path.as_ref().to_str().unwrap();
-    excludes.iter().any(|exclude| origin_path.contains(exclude))
+/// file names which are ignored wherever they are, whatever the ignore files say.
+const ALWAYS_IGNORED: [&str; 2] = [UGIT_REPOSITORY_NAME, ".git"];
 
-    // origin_path
-    //     .as_ref()
-    //     .to_str()
-    //     .unwrap()
-    //     .contains(UGIT_REPOSITORY_NAME)
+/// the source of the patterns which are not read from a file, see [`IgnorePattern::built_in`].
+pub const BUILT_IN_SOURCE: &str = "<built-in>";
+
+/// the default global excludes file, which applies to every repository of the user:
+/// `ignore` in [`dirs::user_config_dir`]. it's replaced by `{EXCLUDES_FILE_KEY}` if it's set.
+pub fn global_excludes_file_path() -> Option<PathBuf> {
+    dirs::user_config_dir().map(|dir| dir.join("ignore"))
+}
+
+/// one pattern of an ignore file, with gitignore semantics:
+///
+/// - blank lines and lines starting with `#` are skipped, `\#` is a literal `#`;
+/// - trailing spaces are dropped unless escaped with `\`;
+/// - a leading `!` re-includes what an earlier pattern ignored, `\!` is a literal `!`;
+/// - a trailing `/` only matches directories;
+/// - a pattern with a `/` at its start or in its middle is anchored to the dir of the
+///   ignore file, otherwise it matches a file name at any level below that dir;
+/// - the rest is a glob, see [`glob::wildmatch`].
+#[derive(Debug, Clone)]
+pub struct IgnorePattern {
+    /// the file the pattern is read from.
+    source: PathBuf,
+    /// 1-based.
+    line_number: usize,
+    /// the line as written in `source`.
+    origin_line: String,
+
+    /// the dir the pattern is relative to, relative to the work tree root.
+    base_dir: PathBuf,
+    glob: Vec<u8>,
+    negated: bool,
+    dir_only: bool,
+    anchored: bool,
+}
+
+impl IgnorePattern {
+    /// Returns [`None`] for lines which hold no pattern.
+    fn parse(line: &[u8], source: &Path, line_number: usize, base_dir: &Path) -> Option<Self> {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let origin_line = String::from_utf8_lossy(line).into_owned();
+
+        if line.starts_with(b"#") {
+            return None;
+        }
+
+        // trailing spaces are dropped, unless the last one is escaped.
+        let mut end = line.len();
+        while end > 0 && line[end - 1] == b' ' {
+            if end >= 2 && line[end - 2] == b'\\' {
+                break;
+            }
+            end -= 1;
+        }
+        let mut pattern = &line[..end];
+
+        let negated = pattern.starts_with(b"!");
+        if negated {
+            pattern = &pattern[1..];
+        }
+
+        let dir_only = pattern.ends_with(b"/");
+        if dir_only {
+            pattern = &pattern[..pattern.len() - 1];
+        }
+
+        let anchored = pattern.contains(&b'/');
+        if let Some(unanchored) = pattern.strip_prefix(b"/") {
+            pattern = unanchored;
+        }
+
+        if pattern.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            source: source.to_path_buf(),
+            line_number,
+            origin_line,
+            base_dir: base_dir.to_path_buf(),
+            glob: pattern.to_vec(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    /// the pattern of a path which is always ignored, shown as
+    /// `{BUILT_IN_SOURCE}:0:{origin_line}`. it's never matched as a glob.
+    fn built_in(origin_line: String) -> Self {
+        Self {
+            source: PathBuf::from(BUILT_IN_SOURCE),
+            line_number: 0,
+            glob: origin_line.clone().into_bytes(),
+            origin_line,
+            base_dir: PathBuf::new(),
+            negated: false,
+            dir_only: false,
+            anchored: false,
+        }
+    }
+
+    /// all patterns of the ignore file at `file`, which is empty if it's not a file.
+    ///
+    /// `source` is how the file is shown, see [`Display`].
+    fn read_all(file: &Path, source: &Path, base_dir: &Path) -> Result<Vec<Self>> {
+        if !file.is_file() {
+            return Ok(vec![]);
+        }
+
+        let content = fs::read(file).with_path(file)?;
+
+        Ok(content
+            .split(|&byte| byte == b'\n')
+            .enumerate()
+            .filter_map(|(index, line)| Self::parse(line, source, index + 1, base_dir))
+            .collect())
+    }
+
+    /// `relative_path` is relative to the work tree root.
+    fn matches(&self, relative_path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+
+        let under_base_dir = match relative_path.strip_prefix(&self.base_dir) {
+            Ok(under_base_dir) => under_base_dir,
+            Err(_) => return false,
+        };
+
+        if self.anchored {
+            glob::wildmatch(&self.glob, under_base_dir.as_os_str().as_bytes())
+        } else {
+            match under_base_dir.file_name() {
+                Some(file_name) => glob::wildmatch(&self.glob, file_name.as_bytes()),
+                None => false,
+            }
+        }
+    }
+
+    /// whether this pattern re-includes what it matches.
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+
+    /// the file the pattern is read from, as shown.
+    pub fn source(&self) -> &Path {
+        &self.source
+    }
+
+    /// 1-based.
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// the line as written in its source.
+    pub fn origin_line(&self) -> &str {
+        &self.origin_line
+    }
+}
+
+/// `{source}:{line_number}:{origin_line}`, where the pattern comes from.
+impl Display for IgnorePattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.source.display(),
+            self.line_number,
+            self.origin_line
+        )
+    }
+}
+
+/// the ignore rules of a work tree, from lowest to highest precedence:
+///
+/// 1. the global excludes file, `{EXCLUDES_FILE_KEY}` of `config` or
+///    [`global_excludes_file_path`];
+/// 2. `{ugit_dir}/info/exclude`, of the repository dir;
+/// 3. the `{IGNORE_FILE_NAME}` of each dir, from the work tree root down to the dir
+///    of the path. deeper files take precedence.
+///
+/// the last matching pattern decides, so a later `!pattern` re-includes a path.
+#[derive(Debug)]
+pub struct IgnoreRules {
+    /// normalized, see [`paths::normalize`].
+    work_tree_root: PathBuf,
+    /// the repository dir, normalized. it's always ignored, whatever its name is.
+    ugit_dir: PathBuf,
+
+    /// the built in patterns of [`ALWAYS_IGNORED`], in the same order.
+    always_ignored: Vec<IgnorePattern>,
+    /// the built in pattern of the repository dir.
+    ugit_dir_pattern: IgnorePattern,
+
+    /// the patterns of the global excludes file and info/exclude.
+    excludes: Vec<IgnorePattern>,
+
+    /// the patterns of the `{IGNORE_FILE_NAME}` of each dir (relative to the work tree root),
+    /// read when a path in the dir is checked for the first time.
+    dir_patterns: HashMap<PathBuf, Vec<IgnorePattern>>,
+}
+
+impl IgnoreRules {
+    /// the rules of the work tree at `work_tree_root`, whose repository dir is `ugit_dir`
+    /// and whose config is `config`.
+    pub fn new<P1: AsRef<Path>, P2: AsRef<Path>>(
+        work_tree_root: P1,
+        ugit_dir: P2,
+        config: &Config,
+    ) -> Result<Self> {
+        let work_tree_root = paths::normalize(work_tree_root)?;
+        let ugit_dir = paths::normalize(ugit_dir)?;
+        let mut excludes = vec![];
+
+        let global_excludes_file = match config.get_path(EXCLUDES_FILE_KEY)? {
+            Some(excludes_file) => Some(excludes_file),
+            None => global_excludes_file_path(),
+        };
+        if let Some(global_excludes_file) = global_excludes_file {
+            excludes.extend(IgnorePattern::read_all(
+                &global_excludes_file,
+                &global_excludes_file,
+                Path::new(""),
+            )?);
+        }
+        // shown relative to the work tree root, like `.ugit-rs/info/exclude`, if it's in there.
+        let info_exclude_file = ugit_dir.join(INFO_EXCLUDE_FILE_NAME);
+        let info_exclude_source = info_exclude_file
+            .strip_prefix(&work_tree_root)
+            .unwrap_or(&info_exclude_file);
+        excludes.extend(IgnorePattern::read_all(
+            &info_exclude_file,
+            info_exclude_source,
+            Path::new(""),
+        )?);
+
+        let always_ignored = ALWAYS_IGNORED
+            .iter()
+            .map(|name| IgnorePattern::built_in(name.to_string()))
+            .collect();
+        let ugit_dir_pattern = IgnorePattern::built_in(
+            ugit_dir
+                .strip_prefix(&work_tree_root)
+                .unwrap_or(&ugit_dir)
+                .to_string_lossy()
+                .into_owned(),
+        );
+
+        Ok(Self {
+            work_tree_root,
+            ugit_dir,
+            always_ignored,
+            ugit_dir_pattern,
+            excludes,
+            dir_patterns: HashMap::new(),
+        })
+    }
+
+    /// the patterns of the `{IGNORE_FILE_NAME}` in `dir`, relative to the work tree root.
+    fn load_dir_patterns(&mut self, dir: &Path) -> Result<()> {
+        if !self.dir_patterns.contains_key(dir) {
+            // shown relative to the work tree root, like `src/.ugitignore`.
+            let source = dir.join(IGNORE_FILE_NAME);
+            let file = self.work_tree_root.join(&source);
+            let patterns = IgnorePattern::read_all(&file, &source, dir)?;
+
+            self.dir_patterns.insert(dir.to_path_buf(), patterns);
+        }
+
+        Ok(())
+    }
+
+    /// the last pattern matching `path`, which is negated if `path` is re-included.
+    /// [`None`] if no pattern matches, or `path` is outside the work tree.
+    ///
+    /// the dirs of `path` are not checked themselves, the tree walk never descends
+    /// into an ignored dir.
+    pub fn matching_pattern<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        is_dir: bool,
+    ) -> Result<Option<&IgnorePattern>> {
+        let relative_path = match paths::normalize(path)?.strip_prefix(&self.work_tree_root) {
+            Ok(relative_path) => relative_path.to_path_buf(),
+            Err(_) => return Ok(None),
+        };
+
+        let dirs: Vec<_> = relative_path.ancestors().skip(1).collect();
+        for dir in &dirs {
+            self.load_dir_patterns(dir)?;
+        }
+
+        let dir_patterns = dirs.iter().rev().flat_map(|dir| &self.dir_patterns[*dir]);
+
+        Ok(self
+            .excludes
+            .iter()
+            .chain(dir_patterns)
+            .rev()
+            .find(|pattern| pattern.matches(&relative_path, is_dir)))
+    }
+
+    /// the built in pattern which ignores `path`, whatever the ignore files say:
+    /// one of [`ALWAYS_IGNORED`] names it, or it's the repository dir.
+    fn built_in_pattern(&self, path: &Path, is_dir: bool) -> Result<Option<&IgnorePattern>> {
+        let file_name = path.file_name().unwrap_or(OsStr::new(""));
+        if let Some(index) = ALWAYS_IGNORED
+            .iter()
+            .position(|always_ignored| file_name == *always_ignored)
+        {
+            return Ok(Some(&self.always_ignored[index]));
+        }
+
+        // a repository dir given by `--ugit-dir` may have any name.
+        if is_dir && paths::normalize(path)? == self.ugit_dir {
+            return Ok(Some(&self.ugit_dir_pattern));
+        }
+
+        Ok(None)
+    }
+
+    /// the pattern which decides whether `path` is ignored, the built in ones first,
+    /// see [`Self::built_in_pattern`] and [`Self::matching_pattern`].
+    fn deciding_pattern(&mut self, path: &Path, is_dir: bool) -> Result<Option<&IgnorePattern>> {
+        if self.built_in_pattern(path, is_dir)?.is_some() {
+            return self.built_in_pattern(path, is_dir);
+        }
+
+        self.matching_pattern(path, is_dir)
+    }
+
+    /// the pattern which explains why `path` is (or is not) ignored, which may be
+    /// a built in one, see [`IgnorePattern::built_in`].
+    ///
+    /// unlike [`Self::is_ignored`], the dirs of `path` in the work tree are checked too,
+    /// since nothing in an ignored dir can be re-included.
+    pub fn explain<P: AsRef<Path>>(&mut self, path: P) -> Result<Option<&IgnorePattern>> {
+        let path = paths::normalize(path)?;
+
+        let mut dirs: Vec<_> = path
+            .ancestors()
+            .skip(1)
+            .filter(|dir| dir.starts_with(&self.work_tree_root) && *dir != self.work_tree_root)
+            .collect();
+        dirs.reverse();
+
+        let mut ignored_dir = None;
+        for dir in dirs {
+            if matches!(self.deciding_pattern(dir, true)?, Some(pattern) if !pattern.is_negated()) {
+                ignored_dir = Some(dir);
+                break;
+            }
+        }
+
+        match ignored_dir {
+            Some(dir) => self.deciding_pattern(dir, true),
+            None => self.deciding_pattern(&path, path.is_dir()),
+        }
+    }
+
+    pub fn is_ignored<P: AsRef<Path>>(&mut self, path: P, is_dir: bool) -> Result<bool> {
+        Ok(match self.deciding_pattern(path.as_ref(), is_dir)? {
+            Some(pattern) => !pattern.is_negated(),
+            None => false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::config::{ConfigFile, ConfigScope};
+    use crate::fs_tools::dirs::CONFIG_FILE_NAME;
+
+    fn pattern(line: &str) -> Option<IgnorePattern> {
+        IgnorePattern::parse(line.as_bytes(), Path::new(".ugitignore"), 1, Path::new(""))
+    }
+
+    fn matches(line: &str, path: &str, is_dir: bool) -> bool {
+        pattern(line).unwrap().matches(Path::new(path), is_dir)
+    }
+
+    #[test]
+    fn lines_without_patterns() {
+        assert!(pattern("").is_none());
+        assert!(pattern("   ").is_none());
+        assert!(pattern("# a comment").is_none());
+        assert!(pattern("/").is_none());
+        assert!(pattern("!").is_none());
+
+        assert!(matches("\\#notes", "#notes", false));
+    }
+
+    #[test]
+    fn trailing_spaces() {
+        assert!(matches("a.txt  ", "a.txt", false));
+        assert!(!matches("a\\ ", "a", false));
+        assert!(matches("a\\ ", "a ", false));
+        assert!(matches("a.txt\r", "a.txt", false));
+    }
+
+    #[test]
+    fn unanchored_patterns_match_names_at_any_level() {
+        assert!(matches("target", "target", true));
+        assert!(matches("target", "sub/target", true));
+        assert!(!matches("target", "src/targeting.rs", false));
+        assert!(matches("*.log", "logs/today.log", false));
+    }
+
+    #[test]
+    fn anchored_patterns() {
+        assert!(matches("/target", "target", true));
+        assert!(!matches("/target", "sub/target", true));
+        assert!(matches("doc/*.txt", "doc/a.txt", false));
+        assert!(!matches("doc/*.txt", "sub/doc/a.txt", false));
+        assert!(!matches("doc/*.txt", "doc/sub/a.txt", false));
+        assert!(matches("doc/**/*.txt", "doc/sub/a.txt", false));
+        assert!(matches("**/build", "a/b/build", true));
+    }
+
+    #[test]
+    fn dir_only_patterns() {
+        assert!(matches("build/", "build", true));
+        assert!(!matches("build/", "build", false));
+        assert!(matches("build/", "sub/build", true));
+    }
+
+    #[test]
+    fn negation() {
+        let negated = pattern("!keep.log").unwrap();
+        assert!(negated.is_negated());
+        assert!(negated.matches(Path::new("keep.log"), false));
+
+        let literal = pattern("\\!important").unwrap();
+        assert!(!literal.is_negated());
+        assert!(literal.matches(Path::new("!important"), false));
+    }
+
+    #[test]
+    fn patterns_are_relative_to_their_dir() {
+        let pattern =
+            IgnorePattern::parse(b"/out", Path::new("sub/.ugitignore"), 3, Path::new("sub"))
+                .unwrap();
+
+        assert!(pattern.matches(Path::new("sub/out"), true));
+        assert!(!pattern.matches(Path::new("out"), true));
+        assert!(!pattern.matches(Path::new("sub/x/out"), true));
+        assert_eq!(pattern.to_string(), "sub/.ugitignore:3:/out");
+    }
+
+    /// a new empty dir in the temp dir.
+    fn tmp_dir() -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = env::temp_dir().join(format!(
+            "ugit-rs-ignored-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn rules_of_all_sources() {
+        let root = tmp_dir();
+        let work_tree = root.join("work");
+        let ugit_dir = work_tree.join(UGIT_REPOSITORY_NAME);
+        fs::create_dir_all(ugit_dir.join("info")).unwrap();
+        fs::create_dir_all(work_tree.join("sub/deep")).unwrap();
+
+        let excludes_file = root.join("global-ignore");
+        fs::write(&excludes_file, "*.bak\n*.tmp\n").unwrap();
+        fs::write(ugit_dir.join(INFO_EXCLUDE_FILE_NAME), "*.log\n!*.tmp\n").unwrap();
+        fs::write(
+            work_tree.join(IGNORE_FILE_NAME),
+            "/out/\ncache\n!keep.log\n",
+        )
+        .unwrap();
+        fs::write(
+            work_tree.join("sub").join(IGNORE_FILE_NAME),
+            "!cache\n*.txt\n",
+        )
+        .unwrap();
+
+        let config_file = ugit_dir.join(CONFIG_FILE_NAME);
+        ConfigFile::edit(&config_file, ConfigScope::Local, |file| {
+            file.set(EXCLUDES_FILE_KEY, &excludes_file.to_string_lossy())
+        })
+        .unwrap();
+        let config = Config::from_iter(
+            ConfigFile::read(&config_file, ConfigScope::Local)
+                .unwrap()
+                .entries()
+                .cloned(),
+        );
+
+        let mut rules = IgnoreRules::new(&work_tree, &ugit_dir, &config).unwrap();
+        let mut is_ignored =
+            |path: &str, is_dir: bool| rules.is_ignored(work_tree.join(path), is_dir).unwrap();
+
+        // the global excludes file, overridden by info/exclude.
+        assert!(is_ignored("a.bak", false));
+        assert!(!is_ignored("a.tmp", false));
+        // info/exclude, overridden by the ignore file of the root.
+        assert!(is_ignored("sub/deep/a.log", false));
+        assert!(!is_ignored("keep.log", false));
+        // the ignore file of the root, overridden by the deeper one.
+        assert!(is_ignored("out", true));
+        assert!(!is_ignored("sub/out", true));
+        assert!(is_ignored("cache", true));
+        assert!(!is_ignored("sub/cache", true));
+        assert!(is_ignored("sub/deep/notes.txt", false));
+        assert!(!is_ignored("notes.txt", false));
+        // the repository dir, whatever the ignore files say.
+        assert!(is_ignored(UGIT_REPOSITORY_NAME, true));
+
+        let pattern = rules
+            .matching_pattern(work_tree.join("sub/cache"), true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(pattern.to_string(), "sub/.ugitignore:1:!cache");
+
+        let mut explain = |path: &str| {
+            rules
+                .explain(work_tree.join(path))
+                .unwrap()
+                .map(|pattern| pattern.to_string())
+        };
+        assert_eq!(explain("out/a.c").as_deref(), Some(".ugitignore:1:/out/"));
+        assert_eq!(
+            explain(".ugit-rs/config").as_deref(),
+            Some("<built-in>:0:.ugit-rs")
+        );
+        assert_eq!(explain("sub/.git").as_deref(), Some("<built-in>:0:.git"));
+        assert_eq!(explain("src/main.rs"), None);
+
+        fs::remove_dir_all(root).unwrap();
+    }
 }
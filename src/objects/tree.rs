@@ -1,125 +1,451 @@
+use crate::crypto::{HashAlgorithm, ObjectId};
+use crate::error::{Error, IoResultExt, Result};
+use crate::fs_tools::pathspec::Pathspec;
 use crate::fs_tools::{files, paths};
 use crate::objects::blob::BlobObject;
-use crate::objects::tree_entry::TreeVitrualFileEntry;
+use crate::objects::file_mode::FileMode;
+use crate::objects::tree_entry::{TreeVirtualFileEntry, TREE_ENTRY_TERMINATOR};
 use crate::objects::type_literal::ObjectTypeLiteral;
-use crate::objects::{Object, OID};
+use crate::objects::Object;
+use std::cmp::Ordering;
+use std::collections::HashSet;
 use std::fmt::{Debug, Formatter};
 use std::fs;
-use std::path::Path;
+use std::io::Cursor;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 
-use super::db::insert::{self, ObjectInsert};
+use super::db::insert::ObjectInsert;
 use super::db::query;
 use super::db::restore::ObjectRestore;
-use super::{ignored, ObjectFileContent, ObjectVirtualContent, OidComputable};
+use super::db::store::StoreRef;
+use super::ignored::IgnoreRules;
+use super::{compute_oid, ObjectVirtualContent, OidComputable};
 
-pub const TREE_ENTRY_SEPARATE_STRING: &str = "\n";
+/// separated the entries of trees written before entries were NUL terminated.
+pub const LEGACY_TREE_ENTRY_SEPARATE_STRING: &str = "\n";
 
-/// a `TreeObject` corresponding a list of `TreeVitrualFileEntry`.
+/// a `TreeObject` corresponding a list of `TreeVirtualFileEntry`.
 ///
 /// # how to restore file origin path, for given `oid`:
 ///
 /// 1. read content of treeobj located in `{UGIT_REPOSITORY_NAME}/oid`;
-/// 2. restore `Vec<TreeVitrualFileEntry>`;
+/// 2. restore `Vec<TreeVirtualFileEntry>`;
 /// 3.
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub struct TreeObject {
-    /// `(origin_file_name, Object)`
-    children: Vec<(String, Object)>,
+    /// the object store this tree is read from and inserted into.
+    store: StoreRef,
+
+    /// `(origin_file_name, mode, Object)`
+    children: Vec<(PathBuf, FileMode, Object)>,
+
+    /// the oid of the tree, once it's computed, stored or read.
+    oid: OnceLock<ObjectId>,
 }
 
+/// the oid of the tree if it's known, and its children. nothing is read from the store
+/// or hashed, see [`Self::entries`] for the oids of all children.
 impl Debug for TreeObject {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        #[derive(Debug)]
-        #[allow(dead_code)]
-        enum HumanReadable<'a> {
-            File {
-                origin_relative_path: &'a String,
-                origin_content: String,
-            },
-            Dir {
-                origin_relative_path: &'a String,
-                its_oid: String,
-            },
-        }
+        f.debug_struct("TreeObject")
+            .field("oid", &self.oid.get())
+            .field("children", &self.children)
+            .finish()
+    }
+}
 
-        let human_readable_children = self
-            .children
+/// the canonical order of tree entries, which is the order Git uses:
+/// names are compared bytewise, as if the name of a directory ended with `/`.
+///
+/// so `a.txt` < `a/` < `a0`, which keeps a tree oid independent of the `fs::read_dir` order.
+pub fn cmp_tree_entries(a: (&Path, FileMode), b: (&Path, FileMode)) -> Ordering {
+    fn sort_key((name, mode): (&Path, FileMode)) -> impl Iterator<Item = u8> + '_ {
+        let dir_suffix = match mode {
+            FileMode::Directory => Some(b'/'),
+            _ => None,
+        };
+
+        name.as_os_str()
+            .as_bytes()
             .iter()
-            .map(|(origin_relative_path, obj)| match obj {
-                Object::BlobObject(blob) => HumanReadable::File {
-                    origin_relative_path,
-                    origin_content: String::from_utf8(blob.origin_content().to_owned()).unwrap(),
-                },
-                Object::TreeObject(tree) => HumanReadable::Dir {
-                    origin_relative_path,
-                    its_oid: tree.oid(),
-                },
-            })
-            .collect::<Vec<_>>();
+            .copied()
+            .chain(dir_suffix)
+    }
 
-        f.debug_struct("TreeObject")
-            .field("children", &human_readable_children)
-            .finish()
+    sort_key(a).cmp(sort_key(b))
+}
+
+/// fails with [`Error::CorruptObject`] if two entries have the same name, whatever their
+/// modes are. restoring both would write the second one through the first one, e.g. the
+/// files of a tree `sub` through a symlink `sub` pointing outside the work tree.
+fn check_unique_names<'a, I: IntoIterator<Item = &'a Path>>(names: I) -> Result<()> {
+    let mut seen = HashSet::new();
+
+    for name in names {
+        if !seen.insert(name) {
+            return Err(Error::corrupt_object(format!(
+                "duplicate tree entry name '{}'",
+                name.display()
+            )));
+        }
     }
+
+    Ok(())
+}
+
+/// fails with [`Error::CorruptObject`] unless `entries` are what a tree is written as:
+/// each entry refers to an object of the type of its mode (see [`FileMode::obj_type`]),
+/// each name is a single path component other than `.` and `..`, no name is used twice,
+/// and the entries are strictly in the canonical order, see [`cmp_tree_entries`].
+fn check_entries(entries: &[TreeVirtualFileEntry]) -> Result<()> {
+    if let Some(entry) = entries
+        .iter()
+        .find(|entry| entry.obj_type() != entry.mode().obj_type())
+    {
+        return Err(Error::corrupt_object(format!(
+            "tree entry '{}' of mode {} is a {}, expected a {}",
+            entry.origin_file_name().display(),
+            entry.mode(),
+            entry.obj_type(),
+            entry.mode().obj_type()
+        )));
+    }
+
+    let names: Vec<_> = entries
+        .iter()
+        .map(TreeVirtualFileEntry::origin_file_name)
+        .collect();
+
+    for name in &names {
+        let bytes = name.as_os_str().as_bytes();
+        if bytes == b"." || bytes == b".." || bytes.contains(&b'/') {
+            return Err(Error::corrupt_object(format!(
+                "unsafe tree entry name '{}'",
+                name.display()
+            )));
+        }
+    }
+
+    check_unique_names(names.iter().map(PathBuf::as_path))?;
+
+    let is_sorted = entries
+        .windows(2)
+        .zip(names.windows(2))
+        .all(|(pair, names)| {
+            cmp_tree_entries((&names[0], pair[0].mode()), (&names[1], pair[1].mode()))
+                == Ordering::Less
+        });
+    if !is_sorted {
+        return Err(Error::corrupt_object("tree entries are not sorted"));
+    }
+
+    Ok(())
 }
 
 impl TreeObject {
-    fn _from_origin_dir<P: AsRef<Path>>(visiting: P, root: &Path) -> Self {
-        let children = fs::read_dir(visiting.as_ref())
-            .unwrap()
-            .map(|entry| entry.unwrap().path())
-            .filter(|path| !ignored::is_ignored(path))
-            .map(|path_under_visiting| {
-                let suffix = match paths::suffix_of(root, path_under_visiting.clone()) {
-                    Some(suffix) => suffix,
-                    None => unreachable!(),
-                };
+    /// sort children into the canonical order, see [`cmp_tree_entries`].
+    fn sort_children(children: &mut [(PathBuf, FileMode, Object)]) {
+        children.sort_by(|(a_name, a_mode, _), (b_name, b_mode, _)| {
+            cmp_tree_entries((a_name, *a_mode), (b_name, *b_mode))
+        });
+    }
+
+    /// directories without any (not ignored) file in them are skipped, like Git does,
+    /// so a tree object never has an empty tree as its child. only the tree of an empty
+    /// root dir is empty.
+    ///
+    /// files matched by `ignore_rules` or not selected by `pathspec` are skipped,
+    /// and dirs which can't hold a selected file are not walked into.
+    ///
+    /// selected files which can't be stored, like FIFOs, sockets and devices, are skipped
+    /// too, and their paths are pushed to `skipped`.
+    fn _from_origin_dir<P: AsRef<Path>>(
+        store: &StoreRef,
+        ignore_rules: &mut IgnoreRules,
+        pathspec: &Pathspec,
+        visiting: P,
+        skipped: &mut Vec<PathBuf>,
+    ) -> Result<Self> {
+        let visiting = visiting.as_ref();
+        let mut children = vec![];
+
+        for entry in fs::read_dir(visiting).with_path(visiting)? {
+            let entry = entry.with_path(visiting)?;
+
+            // only the basename is stored, the full path is known while walking.
+            let origin_file_name = PathBuf::from(entry.file_name());
+            let path_under_visiting = entry.path();
 
-                let origin_file_name_of_suffix = suffix.as_path().to_str().unwrap().to_owned();
+            // symlinks are not followed.
+            let metadata =
+                fs::symlink_metadata(&path_under_visiting).with_path(&path_under_visiting)?;
+            let mode = FileMode::from_metadata(&metadata);
 
-                if path_under_visiting.is_dir() {
-                    let tree_obj = Self::_from_origin_dir(path_under_visiting.clone(), root);
-                    (origin_file_name_of_suffix, Object::TreeObject(tree_obj))
-                } else {
-                    // should be file
-                    let blob_obj = BlobObject::from_origin_path(path_under_visiting.clone());
-                    (origin_file_name_of_suffix, Object::BlobObject(blob_obj))
+            if ignore_rules.is_ignored(&path_under_visiting, mode == Some(FileMode::Directory))? {
+                continue;
+            }
+
+            let mode = match mode {
+                Some(mode) => mode,
+                None => {
+                    // like Git, they are left out of the tree.
+                    if pathspec.matches(&path_under_visiting) {
+                        skipped.push(path_under_visiting);
+                    }
+                    continue;
                 }
-            })
-            .collect();
+            };
+
+            let selected = match mode {
+                FileMode::Directory => pathspec.may_match_under(&path_under_visiting),
+                _ => pathspec.matches(&path_under_visiting),
+            };
+            if !selected {
+                continue;
+            }
+
+            let obj = match mode {
+                FileMode::Directory => {
+                    let tree = Self::_from_origin_dir(
+                        store,
+                        ignore_rules,
+                        pathspec,
+                        &path_under_visiting,
+                        skipped,
+                    )?;
+                    if tree.is_empty() {
+                        continue;
+                    }
+
+                    Object::TreeObject(tree)
+                }
+                FileMode::Symlink => Object::BlobObject(BlobObject::from_symlink(
+                    store.clone(),
+                    &path_under_visiting,
+                )?),
+                FileMode::Regular | FileMode::Executable => Object::BlobObject(
+                    BlobObject::from_origin_path(store.clone(), &path_under_visiting),
+                ),
+            };
+
+            children.push((origin_file_name, mode, obj));
+        }
+
+        Self::sort_children(&mut children);
 
-        Self { children }
+        Ok(Self {
+            store: store.clone(),
+            children,
+            oid: OnceLock::new(),
+        })
     }
 
     /// dir(and files in it) with given path -> Self
     ///
     /// # PERF: expensive, this fn was impled by walkthrough the `origin_root_dir`
     ///
-    pub fn from_origin_dir<P: AsRef<Path>>(origin_root_dir: P) -> Self {
-        Self::_from_origin_dir(origin_root_dir.as_ref(), origin_root_dir.as_ref())
+    /// only the files selected by `pathspec` and not ignored by `ignore_rules` are in it,
+    /// both belong to the work tree `origin_root_dir` is in. the paths of the selected files
+    /// which are not regular files, symlinks or dirs are pushed to `skipped`.
+    pub fn from_origin_dir<P: AsRef<Path>>(
+        store: StoreRef,
+        origin_root_dir: P,
+        ignore_rules: &mut IgnoreRules,
+        pathspec: &Pathspec,
+        skipped: &mut Vec<PathBuf>,
+    ) -> Result<Self> {
+        Self::_from_origin_dir(
+            &store,
+            ignore_rules,
+            pathspec,
+            origin_root_dir.as_ref(),
+            skipped,
+        )
     }
 
-    pub fn from_obj_content(tree_obj_content: Vec<u8>) -> Self {
-        // tree obj content must be UTF-8 chars.
-        let tree_obj_content = String::from_utf8(tree_obj_content).unwrap();
+    /// parse the entries of the virtual content of a tree object.
+    ///
+    /// the content of trees written before entries were NUL terminated has no NUL at all.
+    /// the content of an empty tree is empty, which has no entries in either format.
+    ///
+    /// NUL terminated entries must be as they are written, see [`check_entries`].
+    pub fn entries_from_obj_content(
+        tree_obj_content: Vec<u8>,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<Vec<TreeVirtualFileEntry>> {
+        if tree_obj_content.is_empty() {
+            Ok(vec![])
+        } else if tree_obj_content.contains(&TREE_ENTRY_TERMINATOR) {
+            // every entry ends with the terminator, so the last split is empty.
+            let entries = tree_obj_content
+                .split(|&byte| byte == TREE_ENTRY_TERMINATOR)
+                .filter(|entry_bytes| !entry_bytes.is_empty())
+                .map(|entry_bytes| {
+                    TreeVirtualFileEntry::restore_from_bytes(entry_bytes, hash_algorithm)
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            check_entries(&entries)?;
+            Ok(entries)
+        } else {
+            // legacy tree obj content must be UTF-8 chars.
+            let tree_obj_content = match String::from_utf8(tree_obj_content) {
+                Ok(tree_obj_content) => tree_obj_content,
+                Err(_) => return Err(Error::corrupt_object("legacy tree is not UTF-8")),
+            };
+
+            tree_obj_content
+                .split(LEGACY_TREE_ENTRY_SEPARATE_STRING)
+                .map(|entry_str| {
+                    TreeVirtualFileEntry::restore_from_legacy_str(entry_str, hash_algorithm)
+                })
+                .collect()
+        }
+    }
 
-        let children = tree_obj_content
-            .split(TREE_ENTRY_SEPARATE_STRING)
-            .map(TreeVitrualFileEntry::restore_from_str)
-            .map(|entry| (entry.origin_file_name(), entry.corresponding_object()))
+    /// [`Self::entries_from_obj_content`], where the entries of legacy trees are normalised.
+    ///
+    /// legacy trees, which were written before entries were sorted, are normalised into
+    /// the canonical order, see [`cmp_tree_entries`]. they must not have two entries with
+    /// the same name either.
+    ///
+    /// trees written before only basenames were stored hold paths relative to the root dir
+    /// of the snapshot (`other/dogs.txt` inside the tree of `other`), only their basenames are kept.
+    fn normalised_entries(
+        tree_obj_content: Vec<u8>,
+        hash_algorithm: HashAlgorithm,
+    ) -> Result<Vec<TreeVirtualFileEntry>> {
+        let mut entries: Vec<_> = Self::entries_from_obj_content(tree_obj_content, hash_algorithm)?
+            .into_iter()
+            .map(|entry| match entry.origin_file_name().file_name() {
+                Some(basename) => Ok(TreeVirtualFileEntry::new(
+                    entry.mode(),
+                    entry.oid(),
+                    PathBuf::from(basename),
+                )),
+                None => Err(Error::corrupt_object("unrecognized object tree entry name")),
+            })
+            .collect::<Result<_>>()?;
+
+        entries.sort_by(|a, b| {
+            cmp_tree_entries(
+                (&a.origin_file_name(), a.mode()),
+                (&b.origin_file_name(), b.mode()),
+            )
+        });
+        let names: Vec<_> = entries
+            .iter()
+            .map(TreeVirtualFileEntry::origin_file_name)
             .collect();
+        check_unique_names(names.iter().map(PathBuf::as_path))?;
+
+        Ok(entries)
+    }
+
+    /// the tree whose virtual content is `tree_obj_content`, with all its subtrees,
+    /// see [`Self::normalised_entries`].
+    pub fn from_obj_content(store: StoreRef, tree_obj_content: Vec<u8>) -> Result<Self> {
+        let children = Self::normalised_entries(tree_obj_content, store.hash_algorithm())?
+            .into_iter()
+            .map(|entry| {
+                let obj = entry.corresponding_object(store.clone())?;
+                Ok((entry.origin_file_name(), entry.mode(), obj))
+            })
+            .collect::<Result<_>>()?;
 
-        Self { children }
+        Ok(Self {
+            store,
+            children,
+            oid: OnceLock::new(),
+        })
     }
 
-    pub fn from_tree_obj_oid(tree_oid: OID) -> Self {
-        let (obj_literal, obj_content_after_type) = query::read_object_file(&tree_oid);
+    /// the tree stored as the object with `oid`, whose content after the type literal
+    /// is `tree_obj_content`.
+    pub(crate) fn from_stored_obj_content(
+        store: StoreRef,
+        oid: ObjectId,
+        tree_obj_content: Vec<u8>,
+    ) -> Result<Self> {
+        let tree =
+            Self::from_obj_content(store, tree_obj_content).map_err(|e| e.in_object(&oid))?;
+        let _ = tree.oid.set(oid);
+
+        Ok(tree)
+    }
+
+    /// whether this tree has no children, see [`Self::_from_origin_dir`].
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// the children of this tree, `(origin_file_name, mode, Object)` in the canonical order.
+    /// the subtrees of a tree read from the store are loaded with it.
+    pub fn children(&self) -> &[(PathBuf, FileMode, Object)] {
+        &self.children
+    }
+
+    /// the blobs of this tree and its subtrees, with their paths relative to this tree.
+    pub fn files(&self) -> Vec<(PathBuf, FileMode, BlobObject)> {
+        self.children
+            .iter()
+            .flat_map(|(origin_file_name, mode, obj)| match obj {
+                Object::BlobObject(blob) => vec![(origin_file_name.clone(), *mode, blob.clone())],
+                Object::TreeObject(tree) => tree
+                    .files()
+                    .into_iter()
+                    .map(|(path, mode, blob)| (origin_file_name.join(path), mode, blob))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// the entries this tree is stored as.
+    pub fn entries(&self) -> Result<Vec<TreeVirtualFileEntry>> {
+        self.children
+            .iter()
+            .map(|(origin_file_name, mode, obj)| {
+                Ok(TreeVirtualFileEntry::new(
+                    *mode,
+                    obj.oid()?,
+                    origin_file_name.clone(),
+                ))
+            })
+            .collect()
+    }
+
+    /// the content after the type literal of the object with `tree_oid`, which must be a tree.
+    fn read_stored_content(store: &StoreRef, tree_oid: ObjectId) -> Result<Vec<u8>> {
+        let (obj_literal, obj_content_after_type) =
+            query::read_object_file(store.as_ref(), &tree_oid)?;
 
         if obj_literal != ObjectTypeLiteral::Tree {
-            panic!("the object of given oid {} is not a tree-object", tree_oid);
+            return Err(Error::TypeMismatch {
+                oid: tree_oid,
+                expected: ObjectTypeLiteral::Tree,
+                actual: obj_literal,
+            });
         }
 
-        Self::from_obj_content(obj_content_after_type)
+        Ok(obj_content_after_type)
+    }
+
+    pub fn from_tree_obj_oid(store: StoreRef, tree_oid: ObjectId) -> Result<Self> {
+        let content = Self::read_stored_content(&store, tree_oid)?;
+
+        Self::from_stored_obj_content(store, tree_oid, content)
+    }
+
+    /// the entries of the tree stored as the object with `tree_oid`, see
+    /// [`Self::normalised_entries`]. unlike [`Self::from_tree_obj_oid`], nothing else is
+    /// read, neither its subtrees nor its blobs.
+    pub fn read_entries(store: &StoreRef, tree_oid: ObjectId) -> Result<Vec<TreeVirtualFileEntry>> {
+        let content = Self::read_stored_content(store, tree_oid)?;
+
+        Self::normalised_entries(content, store.hash_algorithm())
+            .map_err(|e| e.in_object(&tree_oid))
     }
 }
 
@@ -130,51 +456,476 @@ impl ObjectVirtualContent for TreeObject {
     /// # Example
     ///
     /// ```plaintext
-    /// blob 91a7b14a584645c7b995100223e65f8a5a33b707 cats.txt
-    /// tree 53891a3c27b17e0f8fd96c058f968d19e340428d other
-    /// blob fa958e0dd2203e9ad56853a3f51e5945dad317a4 other/dogs.txt
+    /// 100644 blob 91a7b14a584645c7b995100223e65f8a5a33b707 cats.txt\0040000 tree 53891a3c27b17e0f8fd96c058f968d19e340428d other\0
     /// ```
-    fn obj_virtual_content(&self) -> Vec<u8> {
-        self.children
+    fn obj_virtual_content(&self) -> Result<Vec<u8>> {
+        Ok(self
+            .entries()?
             .iter()
-            .map(|(origin_file_name, obj)| {
-                TreeVitrualFileEntry::from_obj(obj.clone(), origin_file_name.clone()).to_string()
-            })
-            .collect::<Vec<_>>()
-            .join(TREE_ENTRY_SEPARATE_STRING)
-            .as_bytes()
-            .to_vec()
+            .flat_map(TreeVirtualFileEntry::to_bytes)
+            .collect())
+    }
+}
+
+/// the oid of a tree stored in (or read from) the database is its stored oid.
+/// otherwise it's computed once, the oids of subtrees are computed once too.
+impl OidComputable for TreeObject {
+    fn oid(&self) -> Result<ObjectId> {
+        if let Some(oid) = self.oid.get() {
+            return Ok(*oid);
+        }
+
+        let oid = compute_oid(self, self.store.hash_algorithm())?;
+        Ok(*self.oid.get_or_init(|| oid))
     }
 }
 
 impl ObjectInsert for TreeObject {
-    fn insert_into_db(&self) {
-        insert::save_into_object_file(&self.obj_file_content(), &self.oid());
+    /// the children are inserted first, and the entries are built from the oids they
+    /// are stored as. so each file is read once, whatever its depth.
+    ///
+    /// a tree whose oid is known and already stored is not inserted again, nor are its
+    /// children, which are always stored before it.
+    fn insert_into_db(&self) -> Result<ObjectId> {
+        if let Some(oid) = self.oid.get() {
+            if self.store.has(oid) {
+                return Ok(*oid);
+            }
+        }
 
-        self.children.iter().for_each(|(_, child)| {
-            child.insert_into_db();
-        })
+        let entries = self
+            .children
+            .iter()
+            .map(|(origin_file_name, mode, child)| {
+                Ok(TreeVirtualFileEntry::new(
+                    *mode,
+                    child.insert_into_db()?,
+                    origin_file_name.clone(),
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut obj_file_content = vec![];
+        obj_file_content.extend(ObjectTypeLiteral::Tree.to_string().as_bytes());
+        obj_file_content.push(super::TYPE_CONTENT_SEPARATOR);
+        obj_file_content.extend(entries.iter().flat_map(TreeVirtualFileEntry::to_bytes));
+
+        let oid = self.store.write(&mut Cursor::new(obj_file_content))?;
+        Ok(*self.oid.get_or_init(|| oid))
     }
 }
 
 impl TreeObject {
-    fn _restore(&self) {
-        for (origin_file_name, obj) in &self.children {
+    /// restore children into `dir`.
+    ///
+    /// only the files selected by `pathspec` are restored.
+    fn _restore(&self, dir: &Path, pathspec: &Pathspec) -> Result<()> {
+        for (origin_file_name, mode, obj) in &self.children {
+            let origin_path = dir.join(origin_file_name);
+
+            let selected = match obj {
+                Object::TreeObject(_) => pathspec.may_match_under(&origin_path),
+                Object::BlobObject(_) => pathspec.matches(&origin_path),
+            };
+            if !selected {
+                continue;
+            }
+
             match obj {
+                Object::BlobObject(blob) if *mode == FileMode::Symlink => {
+                    files::store_symlink(&origin_path, &blob.origin_content()?)?
+                }
                 Object::BlobObject(blob) => {
-                    files::store_file(origin_file_name, &blob.origin_content())
+                    files::store_file(
+                        &origin_path,
+                        &mut blob.origin_reader()?,
+                        mode.permissions(),
+                    )?;
                 }
                 Object::TreeObject(tree) => {
-                    tree._restore();
+                    tree._restore(&origin_path, pathspec)?;
                 }
             }
         }
+
+        Ok(())
+    }
+
+    /// restore the files selected by `pathspec` into the work tree `dir`, overwriting them.
+    /// unlike [`ObjectRestore::restore`], other files in `dir` are left alone.
+    pub fn restore_matching(&self, dir: &Path, pathspec: &Pathspec) -> Result<()> {
+        self._restore(dir, pathspec)
     }
 }
 
 impl ObjectRestore for TreeObject {
-    fn restore(&self) {
-        paths::empty_cwd();
-        self._restore();
+    fn restore(&self, dir: &Path, ugit_dir: &Path, kept_names: &[&str]) -> Result<()> {
+        paths::empty_work_tree(dir, ugit_dir, kept_names)?;
+        self._restore(dir, &Pathspec::all(dir)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::os::unix::net::UnixListener;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::config::{Config, ConfigFile, ConfigScope};
+    use crate::fs_tools::dirs::UGIT_REPOSITORY_NAME;
+    use crate::objects::db::memory::MemoryObjectStore;
+
+    fn store() -> StoreRef {
+        Arc::new(MemoryObjectStore::new(HashAlgorithm::Sha1))
+    }
+
+    /// a new empty dir in the temp dir.
+    fn tmp_dir() -> PathBuf {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let dir = env::temp_dir().join(format!(
+            "ugit-rs-tree-{}-{}",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(dir.join(UGIT_REPOSITORY_NAME)).unwrap();
+
+        dir
+    }
+
+    /// the tree of all the files of `work_tree`, whose global excludes file doesn't exist.
+    fn build(store: &StoreRef, work_tree: &Path, skipped: &mut Vec<PathBuf>) -> TreeObject {
+        let ugit_dir = work_tree.join(UGIT_REPOSITORY_NAME);
+        let config_file = ugit_dir.join("config");
+        fs::write(
+            &config_file,
+            format!(
+                "[core]\n\texcludesFile = {}\n",
+                ugit_dir.join("none").display()
+            ),
+        )
+        .unwrap();
+        let config = Config::from_iter(
+            ConfigFile::read(&config_file, ConfigScope::Local)
+                .unwrap()
+                .entries()
+                .cloned(),
+        );
+
+        let mut ignore_rules = IgnoreRules::new(work_tree, &ugit_dir, &config).unwrap();
+        let pathspec = Pathspec::all(work_tree).unwrap();
+
+        TreeObject::from_origin_dir(
+            store.clone(),
+            work_tree,
+            &mut ignore_rules,
+            &pathspec,
+            skipped,
+        )
+        .unwrap()
+    }
+
+    /// the content of a tree with `entries` in the given order, whose objects are in `store`.
+    fn tree_content(store: &StoreRef, entries: &[(FileMode, &str)]) -> Vec<u8> {
+        let blob_oid = BlobObject::from_content(store.clone(), b"/etc".to_vec())
+            .insert_into_db()
+            .unwrap();
+        let tree_oid = TreeObject::from_obj_content(store.clone(), vec![])
+            .unwrap()
+            .insert_into_db()
+            .unwrap();
+
+        entries
+            .iter()
+            .flat_map(|(mode, name)| {
+                let oid = match mode {
+                    FileMode::Directory => tree_oid,
+                    _ => blob_oid,
+                };
+                TreeVirtualFileEntry::new(*mode, oid, PathBuf::from(name)).to_bytes()
+            })
+            .collect()
+    }
+
+    fn parse(store: &StoreRef, entries: &[(FileMode, &str)]) -> Result<TreeObject> {
+        TreeObject::from_obj_content(store.clone(), tree_content(store, entries))
+    }
+
+    #[test]
+    fn entries_are_sorted_like_git() {
+        let mut names = [
+            (PathBuf::from("a0"), FileMode::Regular),
+            (PathBuf::from("a"), FileMode::Directory),
+            (PathBuf::from("a.txt"), FileMode::Regular),
+            (PathBuf::from("A"), FileMode::Symlink),
+        ];
+        names.sort_by(|a, b| cmp_tree_entries((&a.0, a.1), (&b.0, b.1)));
+
+        let names: Vec<_> = names
+            .iter()
+            .map(|(name, _)| name.to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["A", "a.txt", "a", "a0"]);
+    }
+
+    #[test]
+    fn sorted_entries_are_read() {
+        let store = store();
+        let entries = [
+            (FileMode::Regular, "a.txt"),
+            (FileMode::Directory, "a"),
+            (FileMode::Symlink, "link"),
+        ];
+
+        let tree = parse(&store, &entries).unwrap();
+        assert_eq!(
+            tree.obj_virtual_content().unwrap(),
+            tree_content(&store, &entries)
+        );
+    }
+
+    #[test]
+    fn unsorted_entries_are_corrupt() {
+        let store = store();
+
+        for entries in [
+            [(FileMode::Regular, "b"), (FileMode::Regular, "a")],
+            [(FileMode::Directory, "a"), (FileMode::Regular, "a.txt")],
+        ] {
+            assert!(matches!(
+                parse(&store, &entries),
+                Err(Error::CorruptObject { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn duplicate_names_are_corrupt() {
+        let store = store();
+
+        for entries in [
+            [(FileMode::Regular, "a"), (FileMode::Regular, "a")],
+            // the symlink would be restored first, and the tree written through it.
+            [(FileMode::Symlink, "sub"), (FileMode::Directory, "sub")],
+        ] {
+            assert!(matches!(
+                parse(&store, &entries),
+                Err(Error::CorruptObject { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn unsafe_names_are_corrupt() {
+        let store = store();
+
+        for name in [".", "..", "a/b", "/etc", "sub/"] {
+            assert!(matches!(
+                parse(&store, &[(FileMode::Directory, name)]),
+                Err(Error::CorruptObject { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn entries_of_another_type_than_their_mode_are_corrupt() {
+        let oid = HashAlgorithm::Sha1.digest(b"");
+
+        for entry_str in [
+            format!("100644 tree {} x", oid),
+            format!("120000 tree {} x", oid),
+            format!("040000 blob {} y", oid),
+            format!("100755 manifest {} z", oid),
+        ] {
+            let content = format!("{}\0", entry_str).into_bytes();
+
+            assert!(
+                matches!(
+                    TreeObject::from_obj_content(store(), content),
+                    Err(Error::CorruptObject { .. })
+                ),
+                "{:?} is checked",
+                entry_str
+            );
+        }
+
+        let entry = TreeVirtualFileEntry::new(FileMode::Executable, oid, PathBuf::from("x"));
+        assert!(check_entries(&[entry]).is_ok());
+    }
+
+    #[test]
+    fn debug_does_not_read_or_hash_children() {
+        let store = store();
+        let tree = TreeObject {
+            store: store.clone(),
+            children: vec![(
+                PathBuf::from("gone.txt"),
+                FileMode::Regular,
+                Object::BlobObject(BlobObject::from_origin_path(store, "/nonexistent/gone.txt")),
+            )],
+            oid: OnceLock::new(),
+        };
+
+        let debug = format!("{:?}", tree);
+        assert!(debug.contains("gone.txt"));
+        assert!(tree.oid.get().is_none());
+    }
+
+    #[test]
+    fn legacy_trees_are_normalised() {
+        let store = store();
+        let blob_oid = BlobObject::from_content(store.clone(), b"dogs".to_vec())
+            .insert_into_db()
+            .unwrap();
+
+        let legacy_content = format!("blob {0} other/dogs.txt\nblob {0} other/cats.txt", blob_oid);
+        let tree =
+            TreeObject::from_obj_content(store.clone(), legacy_content.into_bytes()).unwrap();
+
+        let names: Vec<_> = tree
+            .entries()
+            .unwrap()
+            .iter()
+            .map(TreeVirtualFileEntry::origin_file_name)
+            .collect();
+        assert_eq!(
+            names,
+            [PathBuf::from("cats.txt"), PathBuf::from("dogs.txt")]
+        );
+
+        let duplicate_content = format!("blob {0} a/dogs.txt\nblob {0} b/dogs.txt", blob_oid);
+        assert!(matches!(
+            TreeObject::from_obj_content(store, duplicate_content.into_bytes()),
+            Err(Error::CorruptObject { .. })
+        ));
+    }
+
+    #[test]
+    fn files_which_cannot_be_stored_are_skipped_and_reported() {
+        let work_tree = tmp_dir();
+        fs::write(work_tree.join("a.txt"), "a").unwrap();
+        fs::create_dir(work_tree.join("sub")).unwrap();
+        let _listener = UnixListener::bind(work_tree.join("sub/socket")).unwrap();
+
+        let mut skipped = vec![];
+        let tree = build(&store(), &work_tree, &mut skipped);
+
+        assert_eq!(skipped, [work_tree.join("sub/socket")]);
+        // `sub` has nothing else, so it's skipped as an empty dir.
+        let names: Vec<_> = tree.children().iter().map(|(name, _, _)| name).collect();
+        assert_eq!(names, [Path::new("a.txt")]);
+
+        fs::remove_dir_all(work_tree).unwrap();
+    }
+
+    #[test]
+    fn entries_are_read_without_subtrees() {
+        let store = store();
+        let missing_oid = HashAlgorithm::Sha1.digest(b"missing");
+        let legacy_content = format!("tree {0} sub\nblob {0} other/a.txt", missing_oid);
+        let mut obj_file_content = b"tree\0".to_vec();
+        obj_file_content.extend(legacy_content.as_bytes());
+        let tree_oid = store.write(&mut Cursor::new(obj_file_content)).unwrap();
+
+        // the subtree is missing, so only its entry can be read.
+        assert!(matches!(
+            TreeObject::from_tree_obj_oid(store.clone(), tree_oid),
+            Err(Error::MissingObject(_))
+        ));
+
+        let entries = TreeObject::read_entries(&store, tree_oid).unwrap();
+        let names: Vec<_> = entries
+            .iter()
+            .map(TreeVirtualFileEntry::origin_file_name)
+            .collect();
+        // normalised like the children of a tree read as a whole.
+        assert_eq!(names, [PathBuf::from("a.txt"), PathBuf::from("sub")]);
+        assert_eq!(entries[1].mode(), FileMode::Directory);
+    }
+
+    #[test]
+    fn nested_entries_are_stored_as_basenames() {
+        let work_tree = tmp_dir();
+        fs::write(work_tree.join("a.txt"), "a").unwrap();
+        fs::create_dir_all(work_tree.join("sub/deep")).unwrap();
+        fs::write(work_tree.join("sub/deep/c.txt"), "c").unwrap();
+
+        let store = store();
+        let tree_oid = build(&store, &work_tree, &mut vec![])
+            .insert_into_db()
+            .unwrap();
+
+        let names = |oid| -> Vec<_> {
+            TreeObject::read_entries(&store, oid)
+                .unwrap()
+                .iter()
+                .map(|entry| (entry.origin_file_name(), entry.oid()))
+                .collect()
+        };
+        let root = names(tree_oid);
+        assert_eq!(root[0].0, PathBuf::from("a.txt"));
+        assert_eq!(root[1].0, PathBuf::from("sub"));
+        let sub = names(root[1].1);
+        assert_eq!(sub.len(), 1);
+        assert_eq!(sub[0].0, PathBuf::from("deep"));
+        let deep = names(sub[0].1);
+        assert_eq!(deep.len(), 1);
+        assert_eq!(deep[0].0, PathBuf::from("c.txt"));
+
+        // read back, the tree has the same paths and is stored the same way.
+        let tree = TreeObject::from_tree_obj_oid(store.clone(), tree_oid).unwrap();
+        let paths: Vec<_> = tree.files().into_iter().map(|(path, _, _)| path).collect();
+        assert_eq!(
+            paths,
+            [PathBuf::from("a.txt"), PathBuf::from("sub/deep/c.txt")]
+        );
+        assert_eq!(tree.oid().unwrap(), tree_oid);
+
+        fs::remove_dir_all(work_tree).unwrap();
+    }
+
+    #[test]
+    fn empty_dirs_are_skipped_but_empty_trees_are_read() {
+        let work_tree = tmp_dir();
+        let store = store();
+
+        // only the repository dir, which isn't part of the tree.
+        let empty = build(&store, &work_tree, &mut vec![]);
+        assert!(empty.is_empty());
+        let empty_oid = empty.insert_into_db().unwrap();
+        assert!(TreeObject::from_tree_obj_oid(store.clone(), empty_oid)
+            .unwrap()
+            .is_empty());
+        assert!(TreeObject::read_entries(&store, empty_oid)
+            .unwrap()
+            .is_empty());
+
+        fs::create_dir(work_tree.join("empty")).unwrap();
+        fs::create_dir_all(work_tree.join("sub/empty")).unwrap();
+        fs::write(work_tree.join("a.txt"), "a").unwrap();
+
+        let mut skipped = vec![];
+        let tree = build(&store, &work_tree, &mut skipped);
+        // skipped as nothing is in them, not reported as files which can't be stored.
+        assert!(skipped.is_empty());
+        let names: Vec<_> = tree.children().iter().map(|(name, _, _)| name).collect();
+        assert_eq!(names, [Path::new("a.txt")]);
+
+        // an empty tree as an entry is still read back.
+        let content = tree_content(&store, &[(FileMode::Directory, "empty")]);
+        let tree = TreeObject::from_obj_content(store.clone(), content).unwrap();
+        match tree.children() {
+            [(name, FileMode::Directory, Object::TreeObject(subtree))] => {
+                assert_eq!(name, Path::new("empty"));
+                assert!(subtree.is_empty());
+                assert_eq!(subtree.oid().unwrap(), empty_oid);
+            }
+            children => panic!("unexpected children {:?}", children),
+        }
+
+        fs::remove_dir_all(work_tree).unwrap();
     }
 }
@@ -1,90 +1,366 @@
+use crate::crypto::{HashAlgorithm, ObjectId};
+use crate::error::{Error, Result};
 use crate::objects::blob::BlobObject;
+use crate::objects::db::store::StoreRef;
+use crate::objects::file_mode::FileMode;
 use crate::objects::tree::TreeObject;
 use crate::objects::type_literal::ObjectTypeLiteral;
-use crate::objects::{Object, OID};
+use crate::objects::Object;
+use std::ffi::OsStr;
 use std::fmt::{Display, Formatter};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
 
 use super::OidComputable;
 
 /// A `TreeObject` corresponds a list of `Self`.
 ///
-/// the `TreeVitrualFileEntry` stored in file with follow format:
-/// `{type literal} {oid} {origin_file_name}`. for example:
-/// - `blob 91a7b14a584645c7b995100223e65f8a5a33b707 cats.txt`
-/// - `tree 53891a3c27b17e0f8fd96c058f968d19e340428d other` (this is a folder)
-/// - `blob fa958e0dd2203e9ad56853a3f51e5945dad317a4 other/dogs.txt`
+/// the `TreeVirtualFileEntry` stored in file with follow format:
+/// `{mode} {type literal} {oid} {origin_file_name}{TREE_ENTRY_TERMINATOR}`. for example:
+/// - `100644 blob 91a7b14a584645c7b995100223e65f8a5a33b707 cats.txt\0`
+/// - `040000 tree 53891a3c27b17e0f8fd96c058f968d19e340428d other\0` (this is a folder)
+/// - `100755 blob fa958e0dd2203e9ad56853a3f51e5945dad317a4 build.sh\0`
+/// - `120000 blob 0d7a4a7d93d3e4b3c43a9c04c4f4b4ea3f1ac5a0 latest\0` (a symlink, the blob holds its target)
+///
+/// `origin_file_name` is the raw bytes of the path, which may contain spaces, newlines
+/// or non-UTF-8 bytes, but never a NUL.
+///
+/// trees written before used `\n` separated entries with whitespace separated fields,
+/// see [`Self::restore_from_legacy_str`].
 
 #[derive(Debug)]
-pub struct TreeVitrualFileEntry {
+pub struct TreeVirtualFileEntry {
+    mode: FileMode,
     obj_type: ObjectTypeLiteral,
-    oid: OID,
+    oid: ObjectId,
 
-    /// for origin file `/xxx/project/f1/abc.txt`, `origin_file_name` will be `f1/abc.txt`.
+    /// for origin file `/xxx/project/f1/abc.txt`, `origin_file_name` will be `abc.txt`,
+    /// in the tree of `f1`. the full path is reconstructed while walking the trees.
     /// the object database will stored in `/xxx/project/{UGIT_REPOSITORY_NAME}`.
-    origin_file_name: String,
+    origin_file_name: PathBuf,
 }
 
-impl Display for TreeVitrualFileEntry {
+/// terminates each entry in the virtual content of a tree object.
+pub const TREE_ENTRY_TERMINATOR: u8 = 0x00;
+
+/// human readable form, `origin_file_name` is shown lossily if it's not UTF-8.
+impl Display for TreeVirtualFileEntry {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{} {} {}",
-            self.obj_type, self.oid, self.origin_file_name
+            "{} {} {}\t{}",
+            self.mode,
+            self.obj_type,
+            self.oid,
+            self.origin_file_name.display()
         )
     }
 }
 
-impl TreeVitrualFileEntry {
-    pub fn from_blob_obj(blob: BlobObject, origin_file_name: String) -> Self {
+impl TreeVirtualFileEntry {
+    /// the entry of the object with `oid`, which is a tree if `mode` is a dir, or a blob.
+    pub fn new(mode: FileMode, oid: ObjectId, origin_file_name: PathBuf) -> Self {
         Self {
-            obj_type: ObjectTypeLiteral::Blob,
-            oid: blob.oid(),
+            mode,
+            obj_type: mode.obj_type(),
+            oid,
             origin_file_name,
         }
     }
 
-    pub fn from_tree_obj(tree: TreeObject, origin_file_name: String) -> Self {
-        Self {
-            obj_type: ObjectTypeLiteral::Tree,
-            oid: tree.oid(),
+    pub fn from_blob_obj(
+        blob: BlobObject,
+        mode: FileMode,
+        origin_file_name: PathBuf,
+    ) -> Result<Self> {
+        Ok(Self::new(mode, blob.oid()?, origin_file_name))
+    }
+
+    pub fn from_tree_obj(tree: TreeObject, origin_file_name: PathBuf) -> Result<Self> {
+        Ok(Self::new(
+            FileMode::Directory,
+            tree.oid()?,
             origin_file_name,
-        }
+        ))
     }
 
-    pub fn from_obj(obj: Object, origin_file_name: String) -> Self {
+    pub fn from_obj(obj: Object, mode: FileMode, origin_file_name: PathBuf) -> Result<Self> {
         match obj {
-            Object::BlobObject(obj) => Self::from_blob_obj(obj, origin_file_name),
+            Object::BlobObject(obj) => Self::from_blob_obj(obj, mode, origin_file_name),
             Object::TreeObject(obj) => Self::from_tree_obj(obj, origin_file_name),
         }
     }
 
-    pub fn restore_from_str(entry_str: &str) -> Self {
-        let mut fields = entry_str.split_whitespace();
+    /// the encoded entry, including its terminator.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut result = vec![];
 
-        let obj_type_str = fields.next().unwrap();
-        let oid_str = fields.next().unwrap();
-        let origin_file_name = fields.next().unwrap();
+        result.extend(format!("{} {} {} ", self.mode, self.obj_type, self.oid).as_bytes());
+        result.extend(self.origin_file_name.as_os_str().as_bytes());
+        result.push(TREE_ENTRY_TERMINATOR);
 
-        if fields.next().is_some() {
-            panic!("unrecognized object tree entry format.");
+        result
+    }
+
+    /// encoded entry, without its terminator -> Self
+    ///
+    /// the type must be the one of the mode, see [`FileMode::obj_type`], as it's written.
+    pub fn restore_from_bytes(entry_bytes: &[u8], hash_algorithm: HashAlgorithm) -> Result<Self> {
+        let mut fields = entry_bytes.splitn(4, |&byte| byte == b' ');
+
+        let mut next_str_field = || match fields.next().map(std::str::from_utf8) {
+            Some(Ok(field)) => Ok(field),
+            _ => Err(Error::corrupt_object(
+                "unrecognized object tree entry format",
+            )),
+        };
+
+        let mode_str = next_str_field()?;
+        let obj_type_str = next_str_field()?;
+        let oid_str = next_str_field()?;
+
+        let origin_file_name = match fields.next() {
+            Some(name) if !name.is_empty() => PathBuf::from(OsStr::from_bytes(name)),
+            _ => {
+                return Err(Error::corrupt_object(
+                    "unrecognized object tree entry format",
+                ))
+            }
+        };
+
+        let mode = match FileMode::try_from(mode_str) {
+            Ok(mode) => mode,
+            Err(_) => return Err(Error::corrupt_object("unknown file mode")),
+        };
+
+        let oid = match ObjectId::from_hex(oid_str, hash_algorithm) {
+            Ok(oid) => oid,
+            Err(e) => {
+                return Err(Error::corrupt_object(format!(
+                    "unrecognized object tree entry oid: {}",
+                    e
+                )))
+            }
+        };
+
+        let obj_type = match ObjectTypeLiteral::try_from(obj_type_str) {
+            Ok(obj_type) => obj_type,
+            Err(_) => return Err(Error::corrupt_object("unknown type literal")),
+        };
+
+        if obj_type != mode.obj_type() {
+            return Err(Error::corrupt_object(format!(
+                "tree entry of mode {} is a {}, expected a {}",
+                mode,
+                obj_type,
+                mode.obj_type()
+            )));
         }
 
+        Ok(Self {
+            mode,
+            obj_type,
+            oid,
+            origin_file_name,
+        })
+    }
+
+    /// entry of a tree written before NUL terminated entries -> Self
+    ///
+    /// `{type literal} {oid} {origin_file_name}`, which has no mode,
+    /// so [`FileMode::default_for`] the type is used.
+    pub fn restore_from_legacy_str(entry_str: &str, hash_algorithm: HashAlgorithm) -> Result<Self> {
+        let mut fields = entry_str.split_whitespace();
+
+        let (obj_type_str, oid_str, origin_file_name) =
+            match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                (Some(obj_type_str), Some(oid_str), Some(origin_file_name), None) => {
+                    (obj_type_str, oid_str, origin_file_name)
+                }
+                _ => {
+                    return Err(Error::corrupt_object(
+                        "unrecognized object tree entry format",
+                    ))
+                }
+            };
+
+        let oid = match ObjectId::from_hex(oid_str, hash_algorithm) {
+            Ok(oid) => oid,
+            Err(e) => {
+                return Err(Error::corrupt_object(format!(
+                    "unrecognized object tree entry oid: {}",
+                    e
+                )))
+            }
+        };
+
         if let Ok(type_literal) = ObjectTypeLiteral::try_from(obj_type_str) {
-            Self {
+            Ok(Self {
+                mode: FileMode::default_for(type_literal),
                 obj_type: type_literal,
-                oid: oid_str.to_string(),
-                origin_file_name: origin_file_name.to_string(),
-            }
+                oid,
+                origin_file_name: PathBuf::from(origin_file_name),
+            })
         } else {
-            panic!("unknown type literal.")
+            Err(Error::corrupt_object("unknown type literal"))
         }
     }
 
-    pub fn origin_file_name(&self) -> String {
+    pub fn mode(&self) -> FileMode {
+        self.mode
+    }
+
+    pub fn obj_type(&self) -> ObjectTypeLiteral {
+        self.obj_type
+    }
+
+    pub fn oid(&self) -> ObjectId {
+        self.oid
+    }
+
+    pub fn origin_file_name(&self) -> PathBuf {
         self.origin_file_name.clone()
     }
 
-    pub fn corresponding_object(&self) -> Object {
-        Object::restore_from_file_with_oid(self.oid.clone())
+    pub fn corresponding_object(&self, store: StoreRef) -> Result<Object> {
+        Object::restore_from_file_with_oid(store, self.oid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(mode: FileMode, name: &[u8]) -> TreeVirtualFileEntry {
+        TreeVirtualFileEntry::new(
+            mode,
+            HashAlgorithm::Sha1.digest(name),
+            PathBuf::from(OsStr::from_bytes(name)),
+        )
+    }
+
+    fn round_trip(entry: &TreeVirtualFileEntry) -> TreeVirtualFileEntry {
+        let bytes = entry.to_bytes();
+        assert_eq!(bytes.last(), Some(&TREE_ENTRY_TERMINATOR));
+
+        TreeVirtualFileEntry::restore_from_bytes(&bytes[..bytes.len() - 1], HashAlgorithm::Sha1)
+            .unwrap()
+    }
+
+    #[test]
+    fn names_round_trip() {
+        let names: [&[u8]; 6] = [
+            b"cats.txt",
+            b"my notes.txt",
+            b" leading and trailing ",
+            b"two\nlines",
+            b"tab\tand\rreturn",
+            b"lat\xe9-1 \xff\xfe",
+        ];
+
+        for name in names {
+            for mode in [FileMode::Regular, FileMode::Symlink, FileMode::Directory] {
+                let entry = entry(mode, name);
+                let restored = round_trip(&entry);
+
+                assert_eq!(restored.origin_file_name.as_os_str().as_bytes(), name);
+                assert_eq!(restored.mode, entry.mode);
+                assert_eq!(restored.obj_type, entry.obj_type);
+                assert_eq!(restored.oid, entry.oid);
+            }
+        }
+    }
+
+    #[test]
+    fn tree_content_round_trips() {
+        let entries = [
+            entry(FileMode::Regular, b"a\nb"),
+            entry(FileMode::Regular, b"my notes.txt"),
+            entry(FileMode::Directory, b"\xe9t\xe9"),
+        ];
+        let content: Vec<u8> = entries
+            .iter()
+            .flat_map(TreeVirtualFileEntry::to_bytes)
+            .collect();
+
+        let restored = TreeObject::entries_from_obj_content(content, HashAlgorithm::Sha1).unwrap();
+        let names: Vec<_> = restored
+            .iter()
+            .map(|entry| entry.origin_file_name.as_os_str().as_bytes())
+            .collect();
+        assert_eq!(names, [&b"a\nb"[..], b"my notes.txt", b"\xe9t\xe9"]);
+    }
+
+    #[test]
+    fn entries_are_encoded_like_git_lists_them() {
+        let entry = entry(FileMode::Executable, b"build.sh");
+
+        assert_eq!(
+            entry.to_bytes(),
+            format!("100755 blob {} build.sh\0", entry.oid).into_bytes()
+        );
+        assert_eq!(
+            entry.to_string(),
+            format!("100755 blob {}\tbuild.sh", entry.oid)
+        );
+    }
+
+    #[test]
+    fn malformed_entries_are_corrupt() {
+        let oid = HashAlgorithm::Sha1.digest(b"");
+        let malformed = [
+            String::new(),
+            format!("100644 blob {}", oid),
+            format!("100644 blob {} ", oid),
+            format!("100644 blob {} a", &oid.to_string()[1..]),
+            format!("100600 blob {} a", oid),
+            format!("100644 commit {} a", oid),
+            format!("blob {} a", oid),
+            format!("100644 tree {} x", oid),
+            format!("120000 tree {} x", oid),
+            format!("040000 blob {} y", oid),
+            format!("100644 manifest {} z", oid),
+        ];
+
+        for entry_str in malformed {
+            assert!(
+                matches!(
+                    TreeVirtualFileEntry::restore_from_bytes(
+                        entry_str.as_bytes(),
+                        HashAlgorithm::Sha1
+                    ),
+                    Err(Error::CorruptObject { .. })
+                ),
+                "{:?} is parsed",
+                entry_str
+            );
+        }
+    }
+
+    #[test]
+    fn oids_of_another_format_are_corrupt() {
+        let entry = entry(FileMode::Regular, b"a");
+        let bytes = entry.to_bytes();
+
+        assert!(TreeVirtualFileEntry::restore_from_bytes(
+            &bytes[..bytes.len() - 1],
+            HashAlgorithm::Sha256
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn legacy_entries_are_read() {
+        let oid = HashAlgorithm::Sha1.digest(b"");
+        let entry = TreeVirtualFileEntry::restore_from_legacy_str(
+            &format!("tree {} other", oid),
+            HashAlgorithm::Sha1,
+        )
+        .unwrap();
+
+        assert_eq!(entry.mode, FileMode::Directory);
+        assert_eq!(entry.obj_type, ObjectTypeLiteral::Tree);
+        assert_eq!(entry.oid, oid);
+        assert_eq!(entry.origin_file_name, PathBuf::from("other"));
     }
 }
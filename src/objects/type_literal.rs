@@ -8,6 +8,9 @@ use std::fmt::{Display, Formatter};
 pub enum ObjectTypeLiteral {
     Blob,
     Tree,
+    /// lists the chunk blobs of a large file, see [`crate::objects::chunked`].
+    #[value(skip)]
+    Manifest,
 }
 
 impl Display for ObjectTypeLiteral {
@@ -15,6 +18,7 @@ impl Display for ObjectTypeLiteral {
         match self {
             ObjectTypeLiteral::Blob => write!(f, "blob"),
             ObjectTypeLiteral::Tree => write!(f, "tree"),
+            ObjectTypeLiteral::Manifest => write!(f, "manifest"),
         }
     }
 }
@@ -35,7 +39,22 @@ macro_rules! impl_to_object_type_literal {
     };
 }
 
-impl_to_object_type_literal!(BlobObject, ObjectTypeLiteral::Blob);
+impl<'a> From<&'a BlobObject> for ObjectTypeLiteral {
+    fn from(blob: &'a BlobObject) -> Self {
+        if blob.is_chunked() {
+            ObjectTypeLiteral::Manifest
+        } else {
+            ObjectTypeLiteral::Blob
+        }
+    }
+}
+
+impl From<BlobObject> for ObjectTypeLiteral {
+    fn from(blob: BlobObject) -> Self {
+        Self::from(&blob)
+    }
+}
+
 impl_to_object_type_literal!(TreeObject, ObjectTypeLiteral::Tree);
 
 impl From<Object> for ObjectTypeLiteral {
@@ -54,6 +73,7 @@ impl<'a> TryFrom<&'a str> for ObjectTypeLiteral {
         match value {
             "blob" => Ok(Self::Blob),
             "tree" => Ok(Self::Tree),
+            "manifest" => Ok(Self::Manifest),
             _ => Err(()),
         }
     }
@@ -0,0 +1,272 @@
+//! A handle of a ugit repository, for programs which embed ugit-rs.
+//!
+//! ```no_run
+//! use ugit_rs::fs_tools::pathspec::Pathspec;
+//! use ugit_rs::Repository;
+//!
+//! # fn main() -> ugit_rs::Result<()> {
+//! let repo = Repository::discover(".")?;
+//!
+//! let pathspec = Pathspec::all(repo.work_tree())?;
+//! let mut skipped = vec![];
+//! let tree_oid = repo.write_tree(&pathspec, &mut skipped)?;
+//! for path in skipped {
+//!     eprintln!("not a regular file, a symlink or a dir: {}", path.display());
+//! }
+//!
+//! let tree = repo.read_tree(tree_oid)?;
+//! for entry in tree.entries()? {
+//!     println!("{}", entry);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::{
+    Config, ConfigFile, ConfigScope, CHUNKED_BLOBS_KEY, OBJECT_FORMAT_KEY, READ_TREE_KEEP_KEY,
+};
+use crate::crypto::{HashAlgorithm, ObjectId};
+use crate::error::{Error, Result};
+use crate::fs_tools::dirs::{self, CONFIG_FILE_NAME, OBJECTS_DIR_NAME, UGIT_REPOSITORY_NAME};
+use crate::fs_tools::paths;
+use crate::fs_tools::pathspec::Pathspec;
+use crate::objects::blob::BlobObject;
+use crate::objects::db::insert::ObjectInsert;
+use crate::objects::db::loose::LooseObjectStore;
+use crate::objects::db::restore::ObjectRestore;
+use crate::objects::db::store::StoreRef;
+use crate::objects::ignored::IgnoreRules;
+use crate::objects::tree::TreeObject;
+use crate::objects::tree_entry::TreeVirtualFileEntry;
+use crate::objects::Object;
+
+/// how [`Repository::init`] sets up a new repository.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InitOptions {
+    /// the hash algorithm used to compute object ids.
+    pub object_format: HashAlgorithm,
+    /// store large files as content-defined chunks, see [`crate::objects::chunked`].
+    pub chunked_blobs: bool,
+}
+
+/// a repository dir (`{UGIT_REPOSITORY_NAME}` by default) and the work tree it snapshots.
+///
+/// both paths are absolute, so a `Repository` does not depend on cwd.
+#[derive(Debug, Clone)]
+pub struct Repository {
+    ugit_dir: PathBuf,
+    work_tree: PathBuf,
+    store: StoreRef,
+    config: Config,
+}
+
+// a `Repository` may be moved to, and shared with, other threads.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Repository>();
+};
+
+impl Repository {
+    /// create the repository dir `ugit_dir` for the work tree `work_tree`.
+    ///
+    /// the options are recorded in the local config, see [`crate::config`].
+    ///
+    /// fails with an [`Error::Io`] of [`std::io::ErrorKind::AlreadyExists`] if `ugit_dir` exists.
+    pub fn init<P1: AsRef<Path>, P2: AsRef<Path>>(
+        ugit_dir: P1,
+        work_tree: P2,
+        options: InitOptions,
+    ) -> Result<Self> {
+        let ugit_dir = ugit_dir.as_ref();
+
+        dirs::create_repo_dir(ugit_dir)?;
+        dirs::create_objects_dir(ugit_dir)?;
+
+        let config_file = ugit_dir.join(CONFIG_FILE_NAME);
+        ConfigFile::edit(config_file, ConfigScope::Local, |config| {
+            config.set(OBJECT_FORMAT_KEY, &options.object_format.to_string())?;
+            if options.chunked_blobs {
+                config.set(CHUNKED_BLOBS_KEY, "true")?;
+            }
+
+            Ok(())
+        })?;
+
+        Self::open(ugit_dir, work_tree)
+    }
+
+    /// the repository with the repository dir `ugit_dir` and the work tree `work_tree`,
+    /// fails with [`Error::NotARepository`] if `ugit_dir` is not an initialized repository dir.
+    ///
+    /// the config of all scopes is read, see [`Config::load`]. repositories whose config
+    /// has no object format use SHA-1.
+    pub fn open<P1: AsRef<Path>, P2: AsRef<Path>>(ugit_dir: P1, work_tree: P2) -> Result<Self> {
+        let ugit_dir = paths::normalize(ugit_dir)?;
+        let work_tree = paths::normalize(work_tree)?;
+
+        if !dirs::is_repo_dir(&ugit_dir) {
+            return Err(Error::NotARepository {
+                path: ugit_dir,
+                searched_parents: false,
+            });
+        }
+
+        let config = Config::load(Some(&ugit_dir))?;
+
+        // the object format of another repository must not apply to this one.
+        let local_object_format = config
+            .get_all(OBJECT_FORMAT_KEY)
+            .filter(|entry| entry.scope() == ConfigScope::Local)
+            .last();
+
+        let hash_algorithm = match local_object_format {
+            Some(entry) => entry.as_object_format()?,
+            None => HashAlgorithm::Sha1,
+        };
+
+        let chunked_blobs = config.get_bool(CHUNKED_BLOBS_KEY)?.unwrap_or(false);
+
+        let store = LooseObjectStore::new(ugit_dir.join(OBJECTS_DIR_NAME), hash_algorithm)
+            .with_chunked_blobs(chunked_blobs);
+
+        Ok(Self {
+            ugit_dir,
+            work_tree,
+            store: Arc::new(store),
+            config,
+        })
+    }
+
+    /// the repository whose work tree is `start` or one of its parent dirs,
+    /// fails with [`Error::NotARepository`] if there is none.
+    pub fn discover<P: AsRef<Path>>(start: P) -> Result<Self> {
+        let start = paths::normalize(start)?;
+
+        match dirs::discover_work_tree_root(&start) {
+            Some(work_tree) => Self::open(work_tree.join(UGIT_REPOSITORY_NAME), work_tree),
+            None => Err(Error::NotARepository {
+                path: start,
+                searched_parents: true,
+            }),
+        }
+    }
+
+    /// the same repository, with its work tree at `work_tree`.
+    pub fn with_work_tree<P: AsRef<Path>>(self, work_tree: P) -> Result<Self> {
+        Ok(Self {
+            work_tree: paths::normalize(work_tree)?,
+            ..self
+        })
+    }
+
+    pub fn ugit_dir(&self) -> &Path {
+        &self.ugit_dir
+    }
+
+    pub fn work_tree(&self) -> &Path {
+        &self.work_tree
+    }
+
+    /// the config of the repository, of all scopes.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// the object database of the repository.
+    pub fn store(&self) -> StoreRef {
+        self.store.clone()
+    }
+
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.store.hash_algorithm()
+    }
+
+    /// the ignore rules of the work tree, see [`IgnoreRules`].
+    pub fn ignore_rules(&self) -> Result<IgnoreRules> {
+        IgnoreRules::new(&self.work_tree, &self.ugit_dir, &self.config)
+    }
+
+    pub fn has_object(&self, oid: &ObjectId) -> bool {
+        self.store.has(oid)
+    }
+
+    /// the object with `oid`, whose content is read on demand.
+    ///
+    /// fails with [`Error::MissingObject`] if there is no such object.
+    pub fn read_object(&self, oid: ObjectId) -> Result<Object> {
+        Object::restore_from_file_with_oid(self.store(), oid)
+    }
+
+    /// store `content` as a blob object, return its oid.
+    pub fn write_blob(&self, content: Vec<u8>) -> Result<ObjectId> {
+        BlobObject::from_content(self.store(), content).insert_into_db()
+    }
+
+    /// store the file at `path` as a blob object (or as chunks), return its oid.
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<ObjectId> {
+        BlobObject::from_origin_path(self.store(), path).insert_into_db()
+    }
+
+    /// the tree of the dir at `dir` in the work tree, with the files selected by `pathspec`.
+    /// nothing is stored yet, see [`ObjectInsert::insert_into_db`].
+    ///
+    /// the paths of the selected files which can't be stored, like FIFOs, sockets and
+    /// devices, are pushed to `skipped`, see [`TreeObject::from_origin_dir`].
+    pub fn build_tree<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        pathspec: &Pathspec,
+        skipped: &mut Vec<PathBuf>,
+    ) -> Result<TreeObject> {
+        TreeObject::from_origin_dir(
+            self.store(),
+            dir,
+            &mut self.ignore_rules()?,
+            pathspec,
+            skipped,
+        )
+    }
+
+    /// store the tree of the whole work tree, with the files selected by `pathspec`.
+    /// the paths of the files which can't be stored are pushed to `skipped`.
+    pub fn write_tree(&self, pathspec: &Pathspec, skipped: &mut Vec<PathBuf>) -> Result<ObjectId> {
+        self.build_tree(&self.work_tree, pathspec, skipped)?
+            .insert_into_db()
+    }
+
+    /// the tree object with `tree_oid`.
+    ///
+    /// fails with [`Error::MissingObject`] if there is no such object,
+    /// or [`Error::TypeMismatch`] if it's not a tree.
+    pub fn read_tree(&self, tree_oid: ObjectId) -> Result<TreeObject> {
+        TreeObject::from_tree_obj_oid(self.store(), tree_oid)
+    }
+
+    /// the entries of the tree object with `tree_oid`, without reading its subtrees
+    /// or blobs, see [`TreeObject::read_entries`].
+    ///
+    /// fails like [`Self::read_tree`].
+    pub fn read_tree_entries(&self, tree_oid: ObjectId) -> Result<Vec<TreeVirtualFileEntry>> {
+        TreeObject::read_entries(&self.store, tree_oid)
+    }
+
+    /// restore `tree` into the work tree.
+    ///
+    /// with an empty `pathspec` the work tree is replaced by `tree` as a whole, except for
+    /// the names of `{READ_TREE_KEEP_KEY}`. otherwise only the selected files are
+    /// overwritten and the rest is kept.
+    pub fn restore_tree(&self, tree: &TreeObject, pathspec: &Pathspec) -> Result<()> {
+        if pathspec.is_empty() {
+            let kept_names = self
+                .config
+                .get_list(READ_TREE_KEEP_KEY, &paths::DEFAULT_KEPT_NAMES)?;
+
+            tree.restore(&self.work_tree, &self.ugit_dir, &kept_names)
+        } else {
+            tree.restore_matching(&self.work_tree, pathspec)
+        }
+    }
+}
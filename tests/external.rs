@@ -0,0 +1,99 @@
+//! dispatching unknown subcommands to `ugit-rs-<name>` executables on PATH.
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// a new empty dir in the temp dir.
+fn tmp_dir() -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let dir = env::temp_dir().join(format!(
+        "ugit-rs-external-cmd-{}-{}",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
+/// run ugit-rs in `cwd` with `args`, where `bin_dir` comes first in PATH, and no config
+/// or repository location is inherited.
+fn ugit(root: &Path, cwd: &Path, bin_dir: &Path, args: &[&str]) -> Output {
+    let mut search_path = vec![bin_dir.to_path_buf()];
+    search_path.extend(env::split_paths(&env::var_os("PATH").unwrap_or_default()));
+
+    Command::new(env!("CARGO_BIN_EXE_ugit-rs"))
+        .args(args)
+        .current_dir(cwd)
+        .env("PATH", env::join_paths(search_path).unwrap())
+        .env("XDG_CONFIG_HOME", root.join("config"))
+        .env_remove("UGIT_DIR")
+        .env_remove("UGIT_WORK_TREE")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn external_commands_get_their_arguments_and_the_repository() {
+    let root = tmp_dir();
+    let (work_tree, bin_dir) = (root.join("work"), root.join("bin"));
+    fs::create_dir_all(work_tree.join("sub")).unwrap();
+    fs::create_dir_all(&bin_dir).unwrap();
+    assert!(ugit(&root, &work_tree, &bin_dir, &["init"])
+        .status
+        .success());
+
+    let script = bin_dir.join("ugit-rs-show");
+    fs::write(
+        &script,
+        "#!/bin/sh\n\
+         printf '%s\\n' \"$@\"\n\
+         printf 'dir=%s\\n' \"$UGIT_DIR\"\n\
+         printf 'work tree=%s\\n' \"$UGIT_WORK_TREE\"\n\
+         printf 'prefix=%s\\n' \"$UGIT_PREFIX\"\n\
+         exit 3\n",
+    )
+    .unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = ugit(
+        &root,
+        &work_tree.join("sub"),
+        &bin_dir,
+        &["show", "a b", "--flag"],
+    );
+
+    // the exit code of the command is the one of ugit-rs.
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "a b\n--flag\ndir={}\nwork tree={}\nprefix=sub/\n",
+            work_tree.join(".ugit-rs").display(),
+            work_tree.display()
+        )
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn unknown_commands_are_usage_errors() {
+    let root = tmp_dir();
+    let bin_dir = root.join("bin");
+    fs::create_dir_all(&bin_dir).unwrap();
+
+    let output = ugit(&root, &root, &bin_dir, &["no-such-command"]);
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("'no-such-command' is not a ugit-rs command"));
+
+    fs::remove_dir_all(root).unwrap();
+}